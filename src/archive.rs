@@ -0,0 +1,196 @@
+use std::{fs, path::Path};
+
+use crate::error::Chip8Error;
+
+// extensions a ROM inside a .zip is recognised by
+#[cfg(feature = "archive")]
+const ROM_EXTENSIONS: [&str; 4] = ["ch8", "c8", "sc8", "xo8"];
+
+// Reads a ROM from disk. A .gz file is decompressed, and from a .zip the
+// first entry with a ROM extension is taken, or its only file if none has
+// one. Anything else is read as it is.
+pub fn read_rom<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, Chip8Error> {
+    let path = path.as_ref();
+    let extension = path
+        .extension()
+        .map(|ext| ext.to_string_lossy().to_ascii_lowercase());
+    match extension.as_deref() {
+        Some("gz") => read_gzip(path),
+        Some("zip") => read_zip(path),
+        _ => Ok(fs::read(path)?),
+    }
+}
+
+#[cfg(feature = "archive")]
+fn is_rom(name: &str) -> bool {
+    Path::new(name).extension().is_some_and(|ext| {
+        ROM_EXTENSIONS
+            .iter()
+            .any(|rom| ext.eq_ignore_ascii_case(rom))
+    })
+}
+
+fn error(reason: String) -> Chip8Error {
+    Chip8Error::Archive { reason }
+}
+
+#[cfg(feature = "archive")]
+fn read_gzip(path: &Path) -> Result<Vec<u8>, Chip8Error> {
+    gunzip(fs::File::open(path)?, path)
+}
+
+#[cfg(feature = "archive")]
+fn read_zip(path: &Path) -> Result<Vec<u8>, Chip8Error> {
+    unzip(fs::File::open(path)?, path)
+}
+
+// path only names the archive in errors
+#[cfg(feature = "archive")]
+fn gunzip<R: std::io::Read>(reader: R, path: &Path) -> Result<Vec<u8>, Chip8Error> {
+    use std::io::Read;
+
+    let mut rom = Vec::new();
+    flate2::read::GzDecoder::new(reader)
+        .read_to_end(&mut rom)
+        .map_err(|err| {
+            error(format!(
+                "{} Is Not A Valid Gzip File : {}",
+                path.display(),
+                err
+            ))
+        })?;
+    Ok(rom)
+}
+
+#[cfg(feature = "archive")]
+fn unzip<R: std::io::Read + std::io::Seek>(reader: R, path: &Path) -> Result<Vec<u8>, Chip8Error> {
+    use std::io::Read;
+
+    let invalid = |err| {
+        error(format!(
+            "{} Is Not A Valid Zip File : {}",
+            path.display(),
+            err
+        ))
+    };
+    let mut archive = zip::ZipArchive::new(reader).map_err(invalid)?;
+
+    let mut files = Vec::new();
+    for idx in 0..archive.len() {
+        let entry = archive.by_index(idx).map_err(invalid)?;
+        if entry.is_file() {
+            files.push((idx, entry.name().to_string()));
+        }
+    }
+    let chosen = match files.iter().find(|(_, name)| is_rom(name)) {
+        Some((idx, _)) => *idx,
+        None if files.len() == 1 => files[0].0,
+        None => {
+            return Err(error(format!(
+                "{} Holds No ROM, Expected One File Or A .{} File",
+                path.display(),
+                ROM_EXTENSIONS.join(", .")
+            )))
+        }
+    };
+
+    let mut rom = Vec::new();
+    archive
+        .by_index(chosen)
+        .map_err(invalid)?
+        .read_to_end(&mut rom)?;
+    Ok(rom)
+}
+
+#[cfg(not(feature = "archive"))]
+fn read_gzip(path: &Path) -> Result<Vec<u8>, Chip8Error> {
+    unsupported(path)
+}
+
+#[cfg(not(feature = "archive"))]
+fn read_zip(path: &Path) -> Result<Vec<u8>, Chip8Error> {
+    unsupported(path)
+}
+
+#[cfg(not(feature = "archive"))]
+fn unsupported(path: &Path) -> Result<Vec<u8>, Chip8Error> {
+    Err(error(format!(
+        "Built Without Archive Support, Cannot Open {}",
+        path.display()
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(feature = "archive")]
+    fn zipped(files: &[(&str, &[u8])]) -> std::io::Cursor<Vec<u8>> {
+        use std::io::Write;
+
+        let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+        for (name, data) in files {
+            writer
+                .start_file(*name, zip::write::FileOptions::default())
+                .unwrap();
+            writer.write_all(data).unwrap();
+        }
+        let mut zip = writer.finish().unwrap();
+        zip.set_position(0);
+        zip
+    }
+
+    #[test]
+    #[cfg(feature = "archive")]
+    fn unzips_the_rom_entry() {
+        let zip = zipped(&[("readme.txt", b"hello"), ("games/pong.ch8", &[0x12, 0x00])]);
+        assert_eq!(unzip(zip, Path::new("pong.zip")).unwrap(), [0x12, 0x00]);
+
+        // without a ROM extension the only file will do
+        let zip = zipped(&[("pong", &[0x00, 0xE0])]);
+        assert_eq!(unzip(zip, Path::new("pong.zip")).unwrap(), [0x00, 0xE0]);
+
+        let zip = zipped(&[("a.txt", b"a"), ("b.txt", b"b")]);
+        assert!(matches!(
+            unzip(zip, Path::new("two.zip")),
+            Err(Chip8Error::Archive { .. })
+        ));
+    }
+
+    #[test]
+    #[cfg(feature = "archive")]
+    fn gunzips_the_rom() {
+        use std::io::Write;
+
+        let mut encoder = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        encoder.write_all(&[0x60, 0x05, 0x12, 0x00]).unwrap();
+        let gz = encoder.finish().unwrap();
+        assert_eq!(
+            gunzip(&gz[..], Path::new("game.ch8.gz")).unwrap(),
+            [0x60, 0x05, 0x12, 0x00]
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "archive")]
+    fn corrupt_archives_are_errors() {
+        let junk = b"PK\x03\x04 this is not really a zip file";
+        assert!(matches!(
+            gunzip(&junk[..], Path::new("junk.gz")),
+            Err(Chip8Error::Archive { .. })
+        ));
+        assert!(matches!(
+            unzip(std::io::Cursor::new(junk), Path::new("junk.zip")),
+            Err(Chip8Error::Archive { .. })
+        ));
+    }
+
+    #[test]
+    #[cfg(not(feature = "archive"))]
+    fn archives_need_the_feature() {
+        assert!(matches!(
+            read_rom("game.zip"),
+            Err(Chip8Error::Archive { .. })
+        ));
+    }
+}
@@ -0,0 +1,788 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use crate::{cpu::OpCode, error::Chip8Error, memory::Memory};
+
+// includes nested deeper than this are assumed to include themselves
+const MAX_INCLUDE_DEPTH: usize = 16;
+// as are constants that take more steps than this to resolve
+const MAX_SYMBOL_DEPTH: usize = 64;
+
+// Assembles Cowgod style mnemonics into a ROM that loads at 0x200.
+//
+//   ; comments run from a semicolon to the end of the line
+//   SPEED equ 2              ; constants, also written SPEED = 2
+//   start:  LD V0, SPEED * 4
+//           LD I, sprite
+//           DRW V0, V1, sprite_end - sprite
+//           JP start
+//   sprite: db 0b11110000, 0x90, "text"
+//   sprite_end:
+//           dw 0x1234, start
+//           include "more.asm"
+//
+// Besides db, dw and include there is org, which pads with zeroes up to an
+// address. Expressions take decimal, 0x / # hex, 0b binary and 'c' numbers,
+// symbols, $ for the address of the line, and the C operators
+// + - * / % & | ^ << >> ~ with parentheses.
+pub fn assemble(source: &str) -> Result<Vec<u8>, Chip8Error> {
+    let mut assembler = Assembler::new();
+    assembler.read(source, "<source>", Path::new("."), 0)?;
+    assembler.emit()
+}
+
+// like assemble, with includes resolved relative to the including file
+pub fn assemble_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, Chip8Error> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path)?;
+    let mut assembler = Assembler::new();
+    assembler.read(
+        &source,
+        &path.display().to_string(),
+        path.parent().unwrap_or(Path::new(".")),
+        0,
+    )?;
+    assembler.emit()
+}
+
+#[derive(Clone)]
+struct Location {
+    file: String,
+    line: usize,
+}
+
+impl Location {
+    fn error(&self, reason: String) -> Chip8Error {
+        Chip8Error::Assembly {
+            location: format!("{}:{}", self.file, self.line),
+            reason,
+        }
+    }
+}
+
+enum Item {
+    Instruction(String, Vec<String>),
+    Bytes(Vec<String>),
+    Words(Vec<String>),
+    // zero padding up to the next line's address
+    Padding(usize),
+}
+
+struct Line {
+    location: Location,
+    address: u16,
+    item: Item,
+}
+
+enum Symbol {
+    Label(u16),
+    Constant(String, Location),
+}
+
+struct Assembler {
+    lines: Vec<Line>,
+    symbols: HashMap<String, Symbol>,
+    address: u16,
+}
+
+impl Assembler {
+    fn new() -> Self {
+        Self {
+            lines: Vec::new(),
+            symbols: HashMap::new(),
+            address: Memory::get_offset() as u16,
+        }
+    }
+
+    // the first pass: lays out every line and collects the symbols
+    fn read(
+        &mut self,
+        source: &str,
+        file: &str,
+        dir: &Path,
+        depth: usize,
+    ) -> Result<(), Chip8Error> {
+        for (idx, text) in source.lines().enumerate() {
+            let location = Location {
+                file: file.to_string(),
+                line: idx + 1,
+            };
+            self.read_line(text, &location, dir, depth)
+                .map_err(|reason| location.error(reason))??;
+        }
+        Ok(())
+    }
+
+    // the outer Result is an error on this line, the inner one an error in an include
+    fn read_line(
+        &mut self,
+        text: &str,
+        location: &Location,
+        dir: &Path,
+        depth: usize,
+    ) -> Result<Result<(), Chip8Error>, String> {
+        let mut text = strip_comment(text).trim();
+
+        // any number of labels may lead the line
+        while let Some((label, rest)) = split_label(text) {
+            self.define(label, Symbol::Label(self.address))?;
+            text = rest.trim();
+        }
+        if text.is_empty() {
+            return Ok(Ok(()));
+        }
+
+        let (name, rest) = text
+            .split_once(char::is_whitespace)
+            .map_or((text, ""), |(name, rest)| (name, rest.trim()));
+
+        // NAME equ expr, or NAME = expr
+        if let Some(value) = rest
+            .strip_prefix('=')
+            .or_else(|| strip_keyword(rest, "equ"))
+        {
+            if !is_symbol(name) {
+                return Err(format!("`{}` Is Not A Valid Constant Name", name));
+            }
+            return self
+                .define(
+                    name,
+                    Symbol::Constant(value.trim().to_string(), location.clone()),
+                )
+                .map(Ok);
+        }
+        if let Some(value) = name.split_once('=') {
+            return Err(format!(
+                "Put Spaces Around The `=` In `{}{}`",
+                value.0, value.1
+            ));
+        }
+
+        let directive = name.trim_start_matches('.').to_ascii_lowercase();
+        let item = match directive.as_str() {
+            "include" => {
+                if depth >= MAX_INCLUDE_DEPTH {
+                    return Err("Includes Are Nested Too Deeply".to_string());
+                }
+                let name = parse_string(rest)
+                    .ok_or_else(|| format!("include Expects A \"file name\", Found `{}`", rest))?;
+                let path: PathBuf = dir.join(String::from_utf8_lossy(&name).as_ref());
+                let source = fs::read_to_string(&path)
+                    .map_err(|e| format!("Cannot Include {} : {}", path.display(), e))?;
+                return Ok(self.read(
+                    &source,
+                    &path.display().to_string(),
+                    path.parent().unwrap_or(dir),
+                    depth + 1,
+                ));
+            }
+            "org" => {
+                let target = self.evaluate(rest, self.address, 0)?;
+                let target = u16::try_from(target)
+                    .ok()
+                    .filter(|target| *target >= self.address)
+                    .ok_or_else(|| {
+                        format!(
+                            "org {:#x} Is Behind The Current Address {:#x}",
+                            target, self.address
+                        )
+                    })?;
+                Item::Padding((target - self.address) as usize)
+            }
+            "db" => Item::Bytes(split_operands(rest)),
+            "dw" => Item::Words(split_operands(rest)),
+            _ => Item::Instruction(name.to_ascii_uppercase(), split_operands(rest)),
+        };
+
+        let size = match &item {
+            Item::Instruction(mnemonic, operands) => instruction_size(mnemonic, operands),
+            Item::Bytes(values) => values
+                .iter()
+                .map(|value| parse_string(value).map_or(1, |bytes| bytes.len()))
+                .sum(),
+            Item::Words(values) => values.len() * 2,
+            Item::Padding(size) => *size,
+        };
+
+        self.lines.push(Line {
+            location: location.clone(),
+            address: self.address,
+            item,
+        });
+        self.address = u16::try_from(self.address as usize + size)
+            .map_err(|_| "Program Runs Past The End Of Memory".to_string())?;
+        Ok(Ok(()))
+    }
+
+    fn define(&mut self, name: &str, symbol: Symbol) -> Result<(), String> {
+        if self.symbols.contains_key(name) {
+            return Err(format!("`{}` Is Already Defined", name));
+        }
+        self.symbols.insert(name.to_string(), symbol);
+        Ok(())
+    }
+
+    // the second pass: now every symbol is known, encode each line
+    fn emit(&self) -> Result<Vec<u8>, Chip8Error> {
+        let mut rom = Vec::new();
+        for line in &self.lines {
+            let error = |reason: String| line.location.error(reason);
+            match &line.item {
+                Item::Instruction(mnemonic, operands) => {
+                    let (op, long) = self
+                        .instruction(mnemonic, operands, line.address)
+                        .map_err(error)?;
+                    rom.extend(u16::from(op).to_be_bytes());
+                    if let Some(long) = long {
+                        rom.extend(long.to_be_bytes());
+                    }
+                }
+                Item::Bytes(values) => {
+                    for value in values {
+                        match parse_string(value) {
+                            Some(bytes) => rom.extend(bytes),
+                            None => rom.push(self.byte(value, line.address).map_err(error)?),
+                        }
+                    }
+                }
+                Item::Words(values) => {
+                    for value in values {
+                        let word = self.evaluate(value, line.address, 0).map_err(error)?;
+                        let word = i16::try_from(word)
+                            .map(|word| word as u16)
+                            .or_else(|_| u16::try_from(word))
+                            .map_err(|_| error(format!("{} Does Not Fit In A Word", word)))?;
+                        rom.extend(word.to_be_bytes());
+                    }
+                }
+                Item::Padding(size) => rom.resize(rom.len() + size, 0),
+            }
+        }
+        Ok(rom)
+    }
+
+    fn evaluate(&self, text: &str, here: u16, depth: usize) -> Result<i64, String> {
+        evaluate(text, &|name| match name {
+            "$" => Ok(here as i64),
+            _ => match self.symbols.get(name) {
+                Some(Symbol::Label(address)) => Ok(*address as i64),
+                Some(Symbol::Constant(_, _)) if depth >= MAX_SYMBOL_DEPTH => {
+                    Err(format!("`{}` Is Defined In Terms Of Itself", name))
+                }
+                // errors deep inside constants point at the outermost one used
+                Some(Symbol::Constant(value, location)) => {
+                    let value = self.evaluate(value, here, depth + 1);
+                    match depth {
+                        0 => value.map_err(|reason| {
+                            format!(
+                                "{} (In `{}` At {}:{})",
+                                reason, name, location.file, location.line
+                            )
+                        }),
+                        _ => value,
+                    }
+                }
+                None => Err(format!("`{}` Is Not Defined", name)),
+            },
+        })
+    }
+
+    fn ranged(&self, text: &str, here: u16, min: i64, max: i64, what: &str) -> Result<i64, String> {
+        let value = self.evaluate(text, here, 0)?;
+        if value < min || value > max {
+            return Err(format!("{} Is Out Of Range For {}", value, what));
+        }
+        Ok(value)
+    }
+
+    // bytes may be written signed, -1 is 0xFF
+    fn byte(&self, text: &str, here: u16) -> Result<u8, String> {
+        Ok(self.ranged(text, here, -128, 0xFF, "A Byte")? as u8)
+    }
+
+    fn address(&self, text: &str, here: u16) -> Result<u16, String> {
+        Ok(self.ranged(text, here, 0, 0xFFF, "An Address")? as u16)
+    }
+
+    fn nibble(&self, text: &str, here: u16) -> Result<u8, String> {
+        Ok(self.ranged(text, here, 0, 0xF, "A Nibble")? as u8)
+    }
+
+    // LD I, LONG addr is the only instruction that carries a second word
+    fn instruction(
+        &self,
+        mnemonic: &str,
+        operands: &[String],
+        here: u16,
+    ) -> Result<(OpCode, Option<u16>), String> {
+        use Operand::*;
+
+        let ops: Vec<Operand> = operands.iter().map(|op| Operand::parse(op)).collect();
+        let addr = |text: &str| self.address(text, here);
+        let byte = |text: &str| self.byte(text, here);
+        let nibble = |text: &str| self.nibble(text, here);
+
+        let op = match (mnemonic, ops.as_slice()) {
+            ("CLS", []) => OpCode::CLEAR,
+            ("RET", []) => OpCode::RETURN,
+            ("SYS", [Value(a)]) => match addr(a)? {
+                0 => OpCode::HALT,
+                a => OpCode::ROUTINE(a),
+            },
+            ("JP", [Value(a)]) => OpCode::GOTO(addr(a)?),
+            ("JP", [V(0), Value(a)]) => OpCode::JUMP(addr(a)?),
+            ("CALL", [Value(a)]) => OpCode::CALL(addr(a)?),
+            ("SE", [V(x), V(y)]) => OpCode::EQ_REG(*x, *y),
+            ("SE", [V(x), Value(b)]) => OpCode::EQ(*x, byte(b)?),
+            ("SNE", [V(x), V(y)]) => OpCode::NEQ_REG(*x, *y),
+            ("SNE", [V(x), Value(b)]) => OpCode::NEQ(*x, byte(b)?),
+            ("LD", [V(x), V(y)]) => OpCode::SET_REG(*x, *y),
+            ("LD", [V(x), Value(b)]) => OpCode::SET_CONST(*x, byte(b)?),
+            ("LD", [I, Long(a)]) => {
+                let long = self.ranged(a, here, 0, 0xFFFF, "A Long Address")? as u16;
+                return Ok((OpCode::LONG_I, Some(long)));
+            }
+            ("LD", [I, Value(a)]) => OpCode::SETI(addr(a)?),
+            ("LD", [V(x), DT]) => OpCode::GET_DELAY(*x),
+            ("LD", [V(x), K]) => OpCode::GET_KEY(*x),
+            ("LD", [DT, V(x)]) => OpCode::SET_DELAY(*x),
+            ("LD", [ST, V(x)]) => OpCode::SET_SOUND(*x),
+            ("LD", [F, V(x)]) => OpCode::SPRI(*x),
+            ("LD", [HF, V(x)]) => OpCode::BIG_SPRI(*x),
+            ("LD", [B, V(x)]) => OpCode::BCP(*x),
+            ("LD", [IndirectI, V(x)]) => OpCode::DUMP(*x),
+            ("LD", [V(x), IndirectI]) => OpCode::LOAD(*x),
+            ("LD", [R, V(x)]) => OpCode::SAVE_FLAGS(*x),
+            ("LD", [V(x), R]) => OpCode::LOAD_FLAGS(*x),
+            ("ADD", [V(x), V(y)]) => OpCode::ADD_REG(*x, *y),
+            ("ADD", [V(x), Value(b)]) => OpCode::ADD_CONST(*x, byte(b)?),
+            ("ADD", [I, V(x)]) => OpCode::ADDI(*x),
+            ("OR", [V(x), V(y)]) => OpCode::OR(*x, *y),
+            ("AND", [V(x), V(y)]) => OpCode::AND(*x, *y),
+            ("XOR", [V(x), V(y)]) => OpCode::XOR(*x, *y),
+            ("SUB", [V(x), V(y)]) => OpCode::SUB_REG(*x, *y),
+            ("SUBN", [V(x), V(y)]) => OpCode::SUBTRACT(*x, *y),
+            // with one operand the shift reads and writes the same register
+            ("SHR", [V(x)]) => OpCode::BSHIFT_RGHT(*x, *x),
+            ("SHR", [V(x), V(y)]) => OpCode::BSHIFT_RGHT(*x, *y),
+            ("SHL", [V(x)]) => OpCode::BSHIFT_LEFT(*x, *x),
+            ("SHL", [V(x), V(y)]) => OpCode::BSHIFT_LEFT(*x, *y),
+            ("RND", [V(x), Value(b)]) => OpCode::RAND(*x, byte(b)?),
+            ("DRW", [V(x), V(y), Value(n)]) => OpCode::DRAW(*x, *y, nibble(n)?),
+            ("SKP", [V(x)]) => OpCode::KEY_P(*x),
+            ("SKNP", [V(x)]) => OpCode::KEY_NP(*x),
+            ("SCD", [Value(n)]) => OpCode::SCROLL_DOWN(nibble(n)?),
+            ("SCR", []) => OpCode::SCROLL_RIGHT,
+            ("SCL", []) => OpCode::SCROLL_LEFT,
+            ("EXIT", []) => OpCode::EXIT,
+            ("LOW", []) => OpCode::LORES,
+            ("HIGH", []) => OpCode::HIRES,
+            ("SAVE", [V(x), V(y)]) => OpCode::SAVE_RANGE(*x, *y),
+            ("LOAD", [V(x), V(y)]) => OpCode::LOAD_RANGE(*x, *y),
+            ("PLANE", [Value(n)]) => OpCode::PLANE(nibble(n)?),
+            ("AUDIO", []) => OpCode::AUDIO,
+            ("PITCH", [V(x)]) => OpCode::PITCH(*x),
+            _ if KNOWN.contains(&mnemonic) => {
+                return Err(format!(
+                    "{} Does Not Take The Operands `{}`",
+                    mnemonic,
+                    operands.join(", ")
+                ))
+            }
+            _ => return Err(format!("Unknown Instruction `{}`", mnemonic)),
+        };
+        Ok((op, None))
+    }
+}
+
+const KNOWN: [&str; 31] = [
+    "CLS", "RET", "SYS", "JP", "CALL", "SE", "SNE", "LD", "ADD", "OR", "AND", "XOR", "SUB", "SUBN",
+    "SHR", "SHL", "RND", "DRW", "SKP", "SKNP", "SCD", "SCR", "SCL", "EXIT", "LOW", "HIGH", "SAVE",
+    "LOAD", "PLANE", "AUDIO", "PITCH",
+];
+
+fn instruction_size(mnemonic: &str, operands: &[String]) -> usize {
+    match (mnemonic, operands) {
+        ("LD", [_, value]) if matches!(Operand::parse(value), Operand::Long(_)) => 4,
+        _ => 2,
+    }
+}
+
+enum Operand<'a> {
+    V(u8),
+    I,
+    IndirectI,
+    DT,
+    ST,
+    K,
+    F,
+    HF,
+    B,
+    R,
+    Long(&'a str),
+    Value(&'a str),
+}
+
+impl<'a> Operand<'a> {
+    fn parse(text: &'a str) -> Self {
+        let upper = text.to_ascii_uppercase();
+        match upper.as_str() {
+            "I" => return Operand::I,
+            "[I]" => return Operand::IndirectI,
+            "DT" => return Operand::DT,
+            "ST" => return Operand::ST,
+            "K" => return Operand::K,
+            "F" => return Operand::F,
+            "HF" => return Operand::HF,
+            "B" => return Operand::B,
+            "R" => return Operand::R,
+            _ => {}
+        }
+        if let Some(reg) = upper
+            .strip_prefix('V')
+            .filter(|reg| reg.len() == 1)
+            .and_then(|reg| u8::from_str_radix(reg, 16).ok())
+        {
+            return Operand::V(reg);
+        }
+        match strip_keyword(text, "long") {
+            Some(value) => Operand::Long(value.trim()),
+            None => Operand::Value(text),
+        }
+    }
+}
+
+// the rest of text after a leading keyword, matched case-insensitively
+fn strip_keyword<'a>(text: &'a str, keyword: &str) -> Option<&'a str> {
+    let head = text.get(..keyword.len())?;
+    let rest = &text[keyword.len()..];
+    (head.eq_ignore_ascii_case(keyword) && rest.starts_with(char::is_whitespace)).then_some(rest)
+}
+
+fn is_symbol(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn split_label(text: &str) -> Option<(&str, &str)> {
+    let (label, rest) = text.split_once(':')?;
+    is_symbol(label.trim()).then_some((label.trim(), rest))
+}
+
+// the positions of quotes matter when looking for `;` and `,`
+fn outside_quotes(text: &str) -> impl Iterator<Item = (usize, char)> + '_ {
+    let mut quote = None;
+    let mut escaped = false;
+    text.char_indices().filter(move |(_, c)| {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if *c == '\\' => escaped = true,
+            Some(q) if *c == q => quote = None,
+            Some(_) => {}
+            None if *c == '"' || *c == '\'' => quote = Some(*c),
+            None => return true,
+        }
+        false
+    })
+}
+
+fn strip_comment(text: &str) -> &str {
+    match outside_quotes(text).find(|(_, c)| *c == ';') {
+        Some((idx, _)) => &text[..idx],
+        None => text,
+    }
+}
+
+fn split_operands(text: &str) -> Vec<String> {
+    if text.trim().is_empty() {
+        return Vec::new();
+    }
+    let mut operands = Vec::new();
+    let mut start = 0;
+    for (idx, _) in outside_quotes(text).filter(|(_, c)| *c == ',') {
+        operands.push(text[start..idx].trim().to_string());
+        start = idx + 1;
+    }
+    operands.push(text[start..].trim().to_string());
+    operands
+}
+
+// the bytes of a "double quoted" string, with \" \\ \n \t and \0 escapes
+fn parse_string(text: &str) -> Option<Vec<u8>> {
+    let inner = text.trim().strip_prefix('"')?.strip_suffix('"')?;
+    let mut bytes = Vec::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '\\' => match chars.next()? {
+                'n' => '\n',
+                't' => '\t',
+                '0' => '\0',
+                other => other,
+            },
+            c => c,
+        };
+        let mut buf = [0; 4];
+        bytes.extend(c.encode_utf8(&mut buf).as_bytes());
+    }
+    Some(bytes)
+}
+
+#[derive(Clone, Debug, PartialEq)]
+enum Token {
+    Number(i64),
+    Symbol(String),
+    Op(&'static str),
+    Open,
+    Close,
+}
+
+const OPERATORS: [&str; 12] = ["<<", ">>", "+", "-", "*", "/", "%", "&", "|", "^", "~", "!"];
+
+fn tokenize(text: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = text.trim();
+
+    while let Some(c) = rest.chars().next() {
+        if c.is_whitespace() {
+            rest = rest.trim_start();
+            continue;
+        }
+        if c == '(' || c == ')' {
+            tokens.push(if c == '(' { Token::Open } else { Token::Close });
+            rest = &rest[1..];
+            continue;
+        }
+        if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(**op)) {
+            tokens.push(Token::Op(op));
+            rest = &rest[op.len()..];
+            continue;
+        }
+        if c == '\'' {
+            let mut chars = rest[1..].chars();
+            let value = match chars.next() {
+                Some('\\') => match chars.next() {
+                    Some('n') => '\n',
+                    Some('t') => '\t',
+                    Some('0') => '\0',
+                    Some(other) => other,
+                    None => return Err("Unterminated Character".to_string()),
+                },
+                Some(value) => value,
+                None => return Err("Unterminated Character".to_string()),
+            };
+            if chars.next() != Some('\'') {
+                return Err("Characters Are Written Like 'A'".to_string());
+            }
+            tokens.push(Token::Number(value as i64));
+            rest = chars.as_str();
+            continue;
+        }
+
+        let end = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == '$' || c == '#'))
+            .unwrap_or(rest.len());
+        if end == 0 {
+            return Err(format!("Unexpected `{}`", c));
+        }
+        let word = &rest[..end];
+        rest = &rest[end..];
+
+        let number = if let Some(hex) = word.strip_prefix("0x").or_else(|| word.strip_prefix('#')) {
+            Some(i64::from_str_radix(hex, 16))
+        } else if let Some(bin) = word.strip_prefix("0b") {
+            Some(i64::from_str_radix(bin, 2))
+        } else if word.starts_with(|c: char| c.is_ascii_digit()) {
+            Some(word.parse())
+        } else {
+            None
+        };
+        match number {
+            Some(Ok(value)) => tokens.push(Token::Number(value)),
+            Some(Err(_)) => return Err(format!("`{}` Is Not A Number", word)),
+            None if word == "$" || is_symbol(word) => tokens.push(Token::Symbol(word.to_string())),
+            None => return Err(format!("`{}` Is Not A Number Or Symbol", word)),
+        }
+    }
+    Ok(tokens)
+}
+
+type Lookup<'a> = dyn Fn(&str) -> Result<i64, String> + 'a;
+
+pub(crate) fn evaluate(text: &str, lookup: &Lookup) -> Result<i64, String> {
+    if text.trim().is_empty() {
+        return Err("Missing Value".to_string());
+    }
+    let tokens = tokenize(text)?;
+    let mut parser = Parser {
+        tokens: &tokens,
+        pos: 0,
+        lookup,
+    };
+    let value = parser.binary(0)?;
+    match parser.tokens.get(parser.pos) {
+        None => Ok(value),
+        Some(token) => Err(format!("Unexpected {:?} In `{}`", token, text.trim())),
+    }
+}
+
+// precedence climbing, loosest binding first, as in C
+const PRECEDENCE: [&[&str]; 5] = [&["|"], &["^"], &["&"], &["<<", ">>"], &["+", "-"]];
+const PRODUCT: &[&str] = &["*", "/", "%"];
+
+struct Parser<'a, 'b> {
+    tokens: &'a [Token],
+    pos: usize,
+    lookup: &'a Lookup<'b>,
+}
+
+impl Parser<'_, '_> {
+    fn peek_op(&self, ops: &[&str]) -> Option<&'static str> {
+        match self.tokens.get(self.pos) {
+            Some(Token::Op(op)) if ops.contains(op) => Some(op),
+            _ => None,
+        }
+    }
+
+    fn binary(&mut self, level: usize) -> Result<i64, String> {
+        let ops = match PRECEDENCE.get(level) {
+            Some(ops) => *ops,
+            None => PRODUCT,
+        };
+        let operand = |parser: &mut Self| {
+            if level < PRECEDENCE.len() {
+                parser.binary(level + 1)
+            } else {
+                parser.unary()
+            }
+        };
+
+        let mut value = operand(self)?;
+        while let Some(op) = self.peek_op(ops) {
+            self.pos += 1;
+            let rhs = operand(self)?;
+            value = match op {
+                "|" => value | rhs,
+                "^" => value ^ rhs,
+                "&" => value & rhs,
+                "<<" => value.checked_shl(rhs as u32).unwrap_or(0),
+                ">>" => value.checked_shr(rhs as u32).unwrap_or(0),
+                "+" => value.wrapping_add(rhs),
+                "-" => value.wrapping_sub(rhs),
+                "*" => value.wrapping_mul(rhs),
+                _ if rhs == 0 => return Err("Division By Zero".to_string()),
+                "/" => value / rhs,
+                _ => value % rhs,
+            };
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<i64, String> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        match token {
+            Some(Token::Op("-")) => Ok(self.unary()?.wrapping_neg()),
+            Some(Token::Op("+")) => self.unary(),
+            Some(Token::Op("~")) => Ok(!self.unary()?),
+            Some(Token::Op("!")) => Ok((self.unary()? == 0) as i64),
+            Some(Token::Number(value)) => Ok(value),
+            Some(Token::Symbol(name)) => (self.lookup)(&name),
+            Some(Token::Open) => {
+                let value = self.binary(0)?;
+                match self.tokens.get(self.pos) {
+                    Some(Token::Close) => {
+                        self.pos += 1;
+                        Ok(value)
+                    }
+                    _ => Err("Missing `)`".to_string()),
+                }
+            }
+            Some(token) => Err(format!("Unexpected {:?}", token)),
+            None => Err("Expression Ends Too Early".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_every_decodable_opcode() {
+        for word in 0..=u16::MAX {
+            let op = OpCode::from(word);
+            if op == OpCode::NONE {
+                continue;
+            }
+            // F000 is followed by its address, as the disassembler writes it
+            let source = match op {
+                OpCode::LONG_I => format!("{} 0x1234", op),
+                _ => op.to_string(),
+            };
+            let rom = assemble(&source)
+                .unwrap_or_else(|err| panic!("{:#06x} `{}` : {}", word, source, err));
+            assert_eq!(rom[..2], word.to_be_bytes(), "{:#06x} `{}`", word, source);
+        }
+    }
+
+    #[test]
+    fn labels_resolve_forwards_and_backwards() {
+        let rom = assemble("start: JP end\nCALL start\nend: JP start").unwrap();
+        assert_eq!(rom, [0x12, 0x04, 0x22, 0x00, 0x12, 0x00]);
+    }
+
+    #[test]
+    fn constants_take_equ_and_equals() {
+        let rom =
+            assemble("SPEED equ 2\nLIMIT = SPEED * 4 + 1\nLD V0, SPEED\nLD V1, LIMIT").unwrap();
+        assert_eq!(rom, [0x60, 0x02, 0x61, 0x09]);
+    }
+
+    #[test]
+    fn data_directives() {
+        let rom = assemble("db 0b11110000, #90, 'A', \"hi\"\ndw 0x1234, here\nhere:").unwrap();
+        assert_eq!(rom, [0xF0, 0x90, 0x41, 0x68, 0x69, 0x12, 0x34, 0x02, 0x09]);
+    }
+
+    #[test]
+    fn org_pads_up_to_an_address() {
+        let rom = assemble("CLS\norg 0x206\nsprite: db 0xFF\nLD I, sprite").unwrap();
+        assert_eq!(rom, [0x00, 0xE0, 0, 0, 0, 0, 0xFF, 0xA2, 0x06]);
+
+        let error = assemble("CLS\norg 0x204\norg 0x202").unwrap_err();
+        assert!(error.to_string().contains("<source>:3"), "{}", error);
+    }
+
+    #[test]
+    fn include_reads_relative_to_the_including_file() {
+        let dir = std::env::temp_dir().join(format!("chip8-include-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("main.asm"),
+            "include \"sprites.asm\"\nLD I, ball\n",
+        )
+        .unwrap();
+        fs::write(dir.join("sprites.asm"), "JP over\nball: db 0x80\nover:\n").unwrap();
+
+        let rom = assemble_file(dir.join("main.asm"));
+        fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(rom.unwrap(), [0x12, 0x03, 0x80, 0xA2, 0x02]);
+    }
+
+    #[test]
+    fn errors_name_the_line() {
+        let error = assemble("CLS\n\nLD V0, missing").unwrap_err().to_string();
+        assert!(error.contains("<source>:3"), "{}", error);
+        assert!(error.contains("missing"), "{}", error);
+
+        let error = assemble("CLS\nFROB V0").unwrap_err().to_string();
+        assert!(error.contains("<source>:2"), "{}", error);
+    }
+}
@@ -0,0 +1,117 @@
+use std::f32::consts::PI;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Waveform {
+    Square,
+    Triangle,
+    Sawtooth,
+    Sine,
+}
+
+impl Waveform {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "square" => Some(Waveform::Square),
+            "triangle" => Some(Waveform::Triangle),
+            "sawtooth" | "saw" => Some(Waveform::Sawtooth),
+            "sine" => Some(Waveform::Sine),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BeeperConfig {
+    pub frequency: f32,
+    // 0.0 is silent, 1.0 is full scale
+    pub volume: f32,
+    pub waveform: Waveform,
+    pub sample_rate: u32,
+}
+
+impl Default for BeeperConfig {
+    fn default() -> Self {
+        Self {
+            frequency: 440.0,
+            volume: 0.25,
+            waveform: Waveform::Square,
+            sample_rate: 44100,
+        }
+    }
+}
+
+// Generates the beeper's samples. Backends own one of these and ask it
+// for however many samples they need.
+pub struct Tone {
+    config: BeeperConfig,
+    phase: f32,
+    playing: bool,
+    // XO-CHIP pattern and the rate, in bits per second, to play it at
+    pattern: Option<([u8; 16], f32)>,
+}
+
+impl Tone {
+    pub fn new(config: BeeperConfig) -> Self {
+        Self {
+            config,
+            phase: 0.0,
+            playing: false,
+            pattern: None,
+        }
+    }
+
+    pub fn get_config(&self) -> &BeeperConfig {
+        &self.config
+    }
+
+    pub fn set_playing(&mut self, playing: bool) {
+        self.playing = playing;
+    }
+
+    pub fn set_pattern(&mut self, pattern: &[u8; 16], pitch: u8) {
+        let rate = 4000.0 * 2f32.powf((pitch as f32 - 64.0) / 48.0);
+        self.pattern = Some((*pattern, rate));
+    }
+
+    pub fn fill(&mut self, out: &mut [f32]) {
+        if !self.playing {
+            out.fill(0.0);
+            return;
+        }
+
+        let step = match self.pattern {
+            Some((_, rate)) => rate / 128.0 / self.config.sample_rate as f32,
+            None => self.config.frequency / self.config.sample_rate as f32,
+        };
+
+        for sample in out.iter_mut() {
+            *sample = self.sample() * self.config.volume;
+            self.phase = (self.phase + step).fract();
+        }
+    }
+
+    // the current sample in -1.0..=1.0
+    fn sample(&self) -> f32 {
+        if let Some((pattern, _)) = &self.pattern {
+            let bit = (self.phase * 128.0) as usize;
+            return if pattern[bit / 8] >> (7 - bit % 8) & 0b1 == 1 {
+                1.0
+            } else {
+                -1.0
+            };
+        }
+
+        match self.config.waveform {
+            Waveform::Square => {
+                if self.phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Triangle => 4.0 * (self.phase - 0.5).abs() - 1.0,
+            Waveform::Sawtooth => 2.0 * self.phase - 1.0,
+            Waveform::Sine => (2.0 * PI * self.phase).sin(),
+        }
+    }
+}
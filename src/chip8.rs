@@ -1,25 +1,61 @@
 use std::{
-    rc::Rc,
-    time::{Duration, Instant},
+    fmt, fs,
+    path::{Path, PathBuf},
 };
 
-use rand::distributions::Bernoulli;
-use sdl2::{event::Event, keyboard::Keycode};
-
 use crate::{
-    cpu::{OpCode, CPU},
+    cpu::CPU,
     debug_levels::DebugLevels,
-    display::{self, Display},
-    keyboard::{self, KeyBoardEvent, Keyboard},
+    display::Display,
+    error::Chip8Error,
+    frontend::{
+        null::{NullAudio, NullInput, NullVideo},
+        AudioSink, InputSource, VideoSink,
+    },
+    keyboard::{KeyBoardEvent, Keyboard},
     memory::Memory,
+    rewind::Rewind,
+    savestate::{StateReader, StateWriter},
+    scheduler::{DEFAULT_IPS, FRAME_RATE},
 };
 
+// a quick save or load that failed, kept until the frontend takes it
+#[derive(Debug)]
+pub enum QuickSaveError {
+    Save(Chip8Error),
+    Load(Chip8Error),
+}
+
+impl fmt::Display for QuickSaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuickSaveError::Save(error) => write!(f, "Quick Save Failed : {}", error),
+            QuickSaveError::Load(error) => write!(f, "Quick Load Failed : {}", error),
+        }
+    }
+}
+
 pub struct CHIP8 {
     cpu: CPU,
     memory: Memory,
     display: Display,
     keyboard: Keyboard,
-    deltatime: Instant,
+    cycles_per_frame: u32,
+    // instructions run so far in the current frame, when driven by step
+    cycle: u32,
+
+    // the quick-save slot, mirrored to quick_save_path when one is set
+    quick_save: Option<Vec<u8>>,
+    quick_save_path: Option<PathBuf>,
+    quick_save_error: Option<QuickSaveError>,
+
+    rewind: Option<Rewind>,
+    // the rewind hotkey is held down
+    rewinding: bool,
+
+    video: Box<dyn VideoSink>,
+    input: Box<dyn InputSource>,
+    audio: Box<dyn AudioSink>,
 }
 
 impl CHIP8 {
@@ -29,70 +65,552 @@ impl CHIP8 {
             memory,
             display,
             keyboard,
-            deltatime: Instant::now(),
+            cycles_per_frame: DEFAULT_IPS / FRAME_RATE,
+            cycle: 0,
+
+            quick_save: None,
+            quick_save_path: None,
+            quick_save_error: None,
+
+            rewind: None,
+            rewinding: false,
+
+            video: Box::new(NullVideo),
+            input: Box::new(NullInput),
+            audio: Box::new(NullAudio),
         }
     }
 
-    #[allow(dead_code)]
+    pub fn set_video(&mut self, video: Box<dyn VideoSink>) {
+        self.video = video;
+    }
+
+    pub fn set_input(&mut self, input: Box<dyn InputSource>) {
+        self.input = input;
+    }
+
+    pub fn set_audio(&mut self, audio: Box<dyn AudioSink>) {
+        self.audio = audio;
+    }
+
+    // completes whatever the audio sink is recording
+    pub fn finish_audio(&mut self) -> Result<(), Chip8Error> {
+        self.audio.finish()
+    }
+
+    pub fn set_ips(&mut self, ips: u32) {
+        self.cycles_per_frame = (ips / FRAME_RATE).max(1);
+    }
+
+    pub fn set_cycles_per_frame(&mut self, cycles: u32) {
+        self.cycles_per_frame = cycles;
+    }
+
+    pub fn get_cycles_per_frame(&self) -> u32 {
+        self.cycles_per_frame
+    }
+
     pub fn get_cpu(&self) -> &CPU {
         &self.cpu
     }
 
-    #[allow(dead_code)]
+    pub fn get_cpu_mut(&mut self) -> &mut CPU {
+        &mut self.cpu
+    }
+
     pub fn get_memory(&self) -> &Memory {
         &self.memory
     }
 
-    pub fn execute(&mut self, debug_levels: DebugLevels) -> Result<(), &str> {
-        if debug_levels.step {
-            loop {
-                match self.keyboard.wait_key(&mut self.display) {
-                    KeyBoardEvent::Quit => return Err("Quit Application"),
-                    KeyBoardEvent::Next => break,
-                    KeyBoardEvent::KeyPressed(_) => {}
-                }
+    pub fn get_memory_mut(&mut self) -> &mut Memory {
+        &mut self.memory
+    }
+
+    pub fn get_display(&self) -> &Display {
+        &self.display
+    }
+
+    pub fn get_keyboard_mut(&mut self) -> &mut Keyboard {
+        &mut self.keyboard
+    }
+
+    pub fn set_quick_save_path<P: AsRef<Path>>(&mut self, path: P) {
+        self.quick_save_path = Some(path.as_ref().to_path_buf());
+    }
+
+    // the latest quick save or load hotkey that failed, for the frontend to report
+    pub fn take_quick_save_error(&mut self) -> Option<QuickSaveError> {
+        self.quick_save_error.take()
+    }
+
+    // the whole machine, less the frontends and quirks, as a versioned blob
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut state = StateWriter::new(self.memory.get_rom_hash());
+        self.cpu.write_state(&mut state);
+        self.memory.write_state(&mut state);
+        self.display.write_state(&mut state);
+        self.keyboard.write_state(&mut state);
+        state.finish()
+    }
+
+    // restores a blob from save_state. It must come from the same version
+    // and ROM, and on any error the machine is left as it was.
+    pub fn load_state(&mut self, bytes: &[u8]) -> Result<(), Chip8Error> {
+        let mut state = StateReader::new(bytes, self.memory.get_rom_hash())?;
+        let mut cpu = self.cpu.clone();
+        let mut memory = self.memory.clone();
+        let mut display = self.display.clone();
+        let mut keyboard = self.keyboard.clone();
+        cpu.read_state(&mut state)?;
+        memory.read_state(&mut state)?;
+        display.read_state(&mut state)?;
+        keyboard.read_state(&mut state)?;
+        state.finish()?;
+
+        self.cpu = cpu;
+        self.memory = memory;
+        self.display = display;
+        self.keyboard = keyboard;
+        self.cycle = 0;
+        Ok(())
+    }
+
+    pub fn save_state_to_file<P: AsRef<Path>>(&self, path: P) -> Result<(), Chip8Error> {
+        fs::write(path, self.save_state())?;
+        Ok(())
+    }
+
+    pub fn load_state_from_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Chip8Error> {
+        self.load_state(&fs::read(path)?)
+    }
+
+    // keeps a snapshot every `interval` frames, `depth` of them at most
+    pub fn set_rewind(&mut self, depth: usize, interval: u32) {
+        self.rewind = Some(Rewind::new(depth, interval));
+    }
+
+    pub fn get_rewind(&self) -> Option<&Rewind> {
+        self.rewind.as_ref()
+    }
+
+    // Steps the machine back to the snapshot at least `frames` old, or the
+    // oldest one kept. The keypad stays as the host has it. Returns how many
+    // frames were undone, 0 when rewinding is off or nothing is recorded.
+    pub fn rewind(&mut self, frames: u32) -> Result<u32, Chip8Error> {
+        let Some((state, rewound)) = self.rewind.as_mut().and_then(|r| r.rewind(frames)) else {
+            return Ok(0);
+        };
+        let keyboard = self.keyboard.clone();
+        self.load_state(&state)?;
+        self.keyboard = keyboard;
+        Ok(rewound)
+    }
+
+    fn quick_save(&mut self) -> Result<(), Chip8Error> {
+        let state = self.save_state();
+        if let Some(path) = &self.quick_save_path {
+            fs::write(path, &state)?;
+        }
+        self.quick_save = Some(state);
+        Ok(())
+    }
+
+    // falls back to the file when nothing was quick-saved this session
+    fn quick_load(&mut self) -> Result<(), Chip8Error> {
+        let state = match (&self.quick_save, &self.quick_save_path) {
+            (Some(state), _) => state.clone(),
+            (None, Some(path)) => fs::read(path)?,
+            (None, None) => return Ok(()),
+        };
+        self.load_state(&state)
+    }
+
+    // runs one 60 Hz frame: a batch of instructions, then a single timer
+    // tick, vertical blank and present
+    pub fn run_frame(&mut self, debug_levels: &DebugLevels) -> Result<(), Chip8Error> {
+        self.begin_frame()?;
+
+        // while the rewind hotkey is held every frame goes back one snapshot
+        if self.rewinding {
+            let interval = self.rewind.as_ref().map_or(1, Rewind::get_interval);
+            self.rewind(interval)?;
+            self.audio.set_tone(false);
+            if self.display.take_dirty() {
+                self.video.present(&self.display);
             }
+            return Ok(());
         }
 
-        self.keyboard.clear_key();
+        for _ in 0..self.cycles_per_frame {
+            self.execute(debug_levels)?;
+            // nothing changes until the frontend delivers a key, so
+            // spend the rest of the frame on timers and the window
+            if self.cpu.is_waiting_for_key() {
+                break;
+            }
+        }
 
-        if !self.cpu.pc_valid() {
-            return Err("Counter Reached End Of Memory");
+        self.end_frame();
+        Ok(())
+    }
+
+    // runs a single instruction, opening and closing frames as run_frame
+    // would, for debuggers that stop between instructions. Returns true
+    // when the instruction finished a frame.
+    pub fn step(&mut self, debug_levels: &DebugLevels) -> Result<bool, Chip8Error> {
+        if self.cycle == 0 {
+            self.begin_frame()?;
         }
-        let instruction = self.cpu.fetch(&mut self.memory);
-        let op_code = self.cpu.decode(instruction);
 
-        let event = self.keyboard.get_events(self.display.events());
+        self.execute(debug_levels)?;
+
+        self.cycle += 1;
+        if self.cycle >= self.cycles_per_frame || self.cpu.is_waiting_for_key() {
+            self.end_frame();
+            self.cycle = 0;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    // the halves of run_frame, for callers that drive the instructions themselves
+    pub fn begin_frame(&mut self) -> Result<(), Chip8Error> {
+        let events = self.input.poll(&mut self.keyboard);
+        if events.contains(&KeyBoardEvent::Quit) {
+            return Err(Chip8Error::Quit);
+        }
+        // a bad state file should not end the session
+        if events.contains(&KeyBoardEvent::SaveState) {
+            if let Err(error) = self.quick_save() {
+                self.quick_save_error = Some(QuickSaveError::Save(error));
+            }
+        }
+        for event in &events {
+            if let KeyBoardEvent::Rewind(held) = event {
+                self.rewinding = *held;
+            }
+        }
+        if events.contains(&KeyBoardEvent::LoadState) {
+            if let Err(error) = self.quick_load() {
+                self.quick_save_error = Some(QuickSaveError::Load(error));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn end_frame(&mut self) {
+        self.cpu.delay_timer();
+        self.cpu.sound_timer();
+        self.cpu.vblank();
+
+        if let Some(pattern) = self.cpu.audio_pattern() {
+            self.audio.set_pattern(pattern, self.cpu.pitch());
+        }
+        self.audio.set_tone(self.cpu.sound_active());
+
+        if self.display.take_dirty() {
+            self.video.present(&self.display);
+        }
 
-        if event.contains(&KeyBoardEvent::Quit) {
-            return Err("Program Quit");
+        if self.rewind.as_mut().is_some_and(Rewind::tick) {
+            let state = self.save_state();
+            if let Some(rewind) = &mut self.rewind {
+                rewind.push(state);
+            }
         }
+    }
 
-        if self.deltatime.elapsed().as_millis() > 107 {
-            self.cpu.delay_timer();
-            self.cpu.sound_timer();
-            self.deltatime = Instant::now()
+    // runs a single instruction, without touching timers or the frontends
+    pub fn execute(&mut self, debug_levels: &DebugLevels) -> Result<(), Chip8Error> {
+        if debug_levels.step {
+            loop {
+                match self.input.wait(&mut self.keyboard) {
+                    KeyBoardEvent::Quit => return Err(Chip8Error::Quit),
+                    KeyBoardEvent::Next => break,
+                    _ => {}
+                }
+            }
         }
-        self.cpu
-            .execute(
-                &mut self.memory,
-                &mut self.display,
-                &mut self.keyboard,
-                &op_code,
-            )
-            .unwrap();
+
+        let instruction = self.cpu.fetch(&mut self.memory)?;
+        let op_code = self.cpu.decode(instruction);
+
+        self.cpu.execute(
+            &mut self.memory,
+            &mut self.display,
+            &mut self.keyboard,
+            &op_code,
+        )?;
 
         if debug_levels.log_all {
             self.cpu.log_last();
         }
 
-        self.display.present();
-
         Ok(())
-        // self.cpu.execute(&mut self.memory)
     }
 
     pub fn log_last(&mut self) {
         self.cpu.log_last();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // presses one hotkey on the first frame
+    struct Press(Option<KeyBoardEvent>);
+
+    impl InputSource for Press {
+        fn poll(&mut self, _keyboard: &mut Keyboard) -> Vec<KeyBoardEvent> {
+            self.0.take().into_iter().collect()
+        }
+
+        fn wait(&mut self, _keyboard: &mut Keyboard) -> KeyBoardEvent {
+            KeyBoardEvent::Next
+        }
+    }
+
+    fn press(event: KeyBoardEvent, path: &Path) -> Option<QuickSaveError> {
+        // JP 0x200
+        let memory = Memory::from_bytes(&[0x12, 0x00]).unwrap();
+        let mut chip8 = CHIP8::create(CPU::new(), memory, Display::new(), Keyboard::new());
+        chip8.set_quick_save_path(path);
+        chip8.set_input(Box::new(Press(Some(event))));
+
+        // the session carries on past the failure
+        chip8.run_frame(&DebugLevels::default()).unwrap();
+        chip8.run_frame(&DebugLevels::default()).unwrap();
+        let error = chip8.take_quick_save_error();
+        assert!(chip8.take_quick_save_error().is_none());
+        error
+    }
+
+    #[test]
+    fn quick_save_failures_are_kept_for_the_frontend() {
+        let dir = std::env::temp_dir().join(format!("chip8-quick-{}", std::process::id()));
+        let missing = dir.join("missing").join("rom.state");
+        assert!(matches!(
+            press(KeyBoardEvent::SaveState, &missing),
+            Some(QuickSaveError::Save(Chip8Error::Io(_)))
+        ));
+
+        fs::create_dir_all(&dir).unwrap();
+        let garbage = dir.join("rom.state");
+        fs::write(&garbage, b"not a state").unwrap();
+        let error = press(KeyBoardEvent::LoadState, &garbage);
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(matches!(
+            error,
+            Some(QuickSaveError::Load(Chip8Error::SaveState { .. }))
+        ));
+    }
+
+    // ADD V0, 1 / JP 0x200 at two instructions a frame, so V0 counts frames
+    fn counter() -> CHIP8 {
+        let memory = Memory::from_bytes(&[0x70, 0x01, 0x12, 0x00]).unwrap();
+        let mut chip8 = CHIP8::create(CPU::new(), memory, Display::new(), Keyboard::new());
+        chip8.set_cycles_per_frame(2);
+        chip8
+    }
+
+    fn frames(chip8: &mut CHIP8, count: u32) {
+        for _ in 0..count {
+            chip8.run_frame(&DebugLevels::default()).unwrap();
+        }
+    }
+
+    #[test]
+    fn rewind_goes_back_to_the_recorded_frame() {
+        let mut chip8 = counter();
+        assert_eq!(chip8.rewind(10).unwrap(), 0);
+
+        // snapshots after frames 1, 3, 5 and 7
+        chip8.set_rewind(10, 2);
+        frames(&mut chip8, 7);
+        assert_eq!(chip8.get_cpu().get_registers()[0], 7);
+
+        chip8.get_keyboard_mut().press(0x5);
+        assert_eq!(chip8.rewind(4).unwrap(), 4);
+        assert_eq!(chip8.get_cpu().get_registers()[0], 3);
+        // the keypad is left as the host has it
+        assert!(chip8.get_keyboard_mut().is_key_pressed(0x5));
+
+        // there is only one older snapshot to go back to
+        assert_eq!(chip8.rewind(100).unwrap(), 2);
+        assert_eq!(chip8.get_cpu().get_registers()[0], 1);
+
+        // running on records over the history that was rewound
+        frames(&mut chip8, 4);
+        assert_eq!(chip8.get_cpu().get_registers()[0], 5);
+        assert_eq!(chip8.rewind(2).unwrap(), 2);
+        assert_eq!(chip8.get_cpu().get_registers()[0], 3);
+    }
+
+    // draws a sprite, starts the delay timer and ends up counting in V2
+    // inside a subroutine, so every section of the state has something in it
+    #[rustfmt::skip]
+    const BUSY: [u8; 20] = [
+        0xA2, 0x0C, // LD I, 0x20C
+        0x61, 0x05, // LD V1, 5
+        0xD1, 0x15, // DRW V1, V1, 5
+        0xF1, 0x15, // LD DT, V1
+        0x22, 0x10, // CALL 0x210
+        0x12, 0x0A, // JP 0x20A
+        0xF0, 0x90, 0xF0, 0x90, // sprite
+        0x72, 0x01, // ADD V2, 1
+        0x12, 0x10, // JP 0x210
+    ];
+
+    fn busy() -> CHIP8 {
+        let memory = Memory::from_bytes(&BUSY).unwrap();
+        let mut chip8 = CHIP8::create(CPU::new(), memory, Display::new(), Keyboard::new());
+        // the VIP waits for the vertical blank after drawing
+        frames(&mut chip8, 2);
+        chip8
+    }
+
+    #[test]
+    fn save_state_round_trips() {
+        let mut chip8 = busy();
+        chip8.get_keyboard_mut().press(0xA);
+        let saved = chip8.save_state();
+        let v2 = chip8.get_cpu().get_registers()[2];
+
+        frames(&mut chip8, 3);
+        chip8.get_keyboard_mut().release(0xA);
+        *chip8.get_memory_mut().get_mut(0x300).unwrap() = 0xFF;
+        assert_ne!(chip8.save_state(), saved);
+
+        chip8.load_state(&saved).unwrap();
+        assert_eq!(chip8.save_state(), saved);
+        assert_eq!(chip8.get_cpu().get_registers()[2], v2);
+        assert_eq!(chip8.get_cpu().get_sp(), 1);
+        assert_eq!(chip8.get_cpu().get_delay(), 4);
+        assert!(chip8.get_display().get_pixel(5, 5));
+        assert!(chip8.get_keyboard_mut().is_key_pressed(0xA));
+        assert_eq!(chip8.get_memory().get(0x300), Some(0));
+    }
+
+    #[test]
+    fn bad_save_states_leave_the_machine_alone() {
+        let mut chip8 = busy();
+        let saved = chip8.save_state();
+        frames(&mut chip8, 2);
+        let current = chip8.save_state();
+
+        let mut magic = saved.clone();
+        magic[..4].copy_from_slice(b"NOPE");
+        let mut version = saved.clone();
+        version[4..6].copy_from_slice(&(crate::savestate::VERSION + 1).to_le_bytes());
+        let truncated = saved[..saved.len() - 10].to_vec();
+        let other_rom = counter().save_state();
+
+        for bad in [magic, version, truncated, other_rom] {
+            assert!(matches!(
+                chip8.load_state(&bad),
+                Err(Chip8Error::SaveState { .. })
+            ));
+            assert_eq!(chip8.save_state(), current);
+        }
+    }
+
+    #[test]
+    fn stack_faults_come_back_from_run_frame() {
+        // CALL 0x200, forever
+        let memory = Memory::from_bytes(&[0x22, 0x00]).unwrap();
+        let mut chip8 = CHIP8::create(CPU::new(), memory, Display::new(), Keyboard::new());
+        chip8.set_cycles_per_frame(20);
+        assert!(matches!(
+            chip8.run_frame(&DebugLevels::default()),
+            Err(Chip8Error::StackOverflow { pc: 0x200 })
+        ));
+
+        // RET with nothing to return to
+        let memory = Memory::from_bytes(&[0x00, 0xEE]).unwrap();
+        let mut chip8 = CHIP8::create(CPU::new(), memory, Display::new(), Keyboard::new());
+        assert!(matches!(
+            chip8.run_frame(&DebugLevels::default()),
+            Err(Chip8Error::StackUnderflow { pc: 0x200 })
+        ));
+    }
+
+    // counts how often the machine presents a frame
+    struct Presents(std::rc::Rc<std::cell::Cell<u32>>);
+
+    impl VideoSink for Presents {
+        fn present(&mut self, _display: &Display) {
+            self.0.set(self.0.get() + 1);
+        }
+    }
+
+    #[test]
+    fn a_frame_runs_the_instruction_budget_and_ticks_once() {
+        let mut chip8 = counter();
+        chip8.set_ips(600);
+        assert_eq!(chip8.get_cycles_per_frame(), 10);
+        chip8.get_cpu_mut().set_delay(10);
+        chip8.get_cpu_mut().set_sound(2);
+
+        frames(&mut chip8, 3);
+        // ten instructions a frame, every other one an ADD
+        assert_eq!(chip8.get_cpu().get_registers()[0], 15);
+        assert_eq!(chip8.get_cpu().get_delay(), 7);
+        assert_eq!(chip8.get_cpu().get_sound(), 0);
+
+        // at least one instruction a frame, however slow
+        chip8.set_ips(30);
+        assert_eq!(chip8.get_cycles_per_frame(), 1);
+    }
+
+    #[test]
+    fn a_frame_presents_once_and_only_when_drawn() {
+        let mut cpu = CPU::with_quirks(crate::quirks::Quirks::chip48());
+        cpu.set_pc(0x200);
+        // DRW V0, V1, 5 / JP 0x200, five draws a frame
+        let memory = Memory::from_bytes(&[0xD0, 0x15, 0x12, 0x00]).unwrap();
+        let mut chip8 = CHIP8::create(cpu, memory, Display::new(), Keyboard::new());
+        let presents = std::rc::Rc::new(std::cell::Cell::new(0));
+        chip8.set_video(Box::new(Presents(presents.clone())));
+        frames(&mut chip8, 4);
+        assert_eq!(presents.get(), 4);
+
+        // nothing drawn, nothing to present
+        let mut chip8 = counter();
+        chip8.set_video(Box::new(Presents(presents.clone())));
+        frames(&mut chip8, 1);
+        presents.set(0);
+        frames(&mut chip8, 4);
+        assert_eq!(presents.get(), 0);
+    }
+
+    #[test]
+    fn get_key_waits_for_a_release_while_timers_run() {
+        // LD V3, K / JP 0x202
+        let memory = Memory::from_bytes(&[0xF3, 0x0A, 0x12, 0x02]).unwrap();
+        let mut chip8 = CHIP8::create(CPU::new(), memory, Display::new(), Keyboard::new());
+        chip8.set_cycles_per_frame(10);
+        chip8.get_cpu_mut().set_delay(5);
+
+        // a key let go before the wait began does not count
+        chip8.get_keyboard_mut().press(0x9);
+        chip8.get_keyboard_mut().release(0x9);
+        frames(&mut chip8, 2);
+        assert!(chip8.get_cpu().is_waiting_for_key());
+        assert_eq!(chip8.get_cpu().get_pc(), 0x200);
+        assert_eq!(chip8.get_cpu().get_delay(), 3);
+
+        // pressing is not enough, the key has to come back up
+        chip8.get_keyboard_mut().press(0x7);
+        frames(&mut chip8, 1);
+        assert!(chip8.get_cpu().is_waiting_for_key());
+        assert_eq!(chip8.get_cpu().get_pc(), 0x200);
+
+        chip8.get_keyboard_mut().release(0x7);
+        frames(&mut chip8, 1);
+        assert!(!chip8.get_cpu().is_waiting_for_key());
+        assert_eq!(chip8.get_cpu().get_registers()[3], 0x7);
+        assert_eq!(chip8.get_cpu().get_pc(), 0x202);
+        assert_eq!(chip8.get_cpu().get_delay(), 1);
+    }
+}
@@ -0,0 +1,348 @@
+use std::path::PathBuf;
+
+use chip8::{
+    audio::{BeeperConfig, Waveform},
+    display::Palette,
+    font, rewind,
+    scheduler::DEFAULT_IPS,
+    Memory, Quirks,
+};
+
+pub const USAGE: &str = "\
+Usage: chip8 [OPTIONS] <ROM>            a .8o ROM is compiled from Octo first,
+                                        .zip and .gz ROMs are unpacked
+       chip8 disasm [--origin <ADDR>] <ROM>
+       chip8 asm [-o <FILE>] <SOURCE>      assembly, or Octo for .8o sources
+
+Options:
+  --ips <N>             Instructions executed per second [default: 600]
+  --quirks <PRESET>     vip, chip48, schip or xochip [default: vip]
+  --stack-depth <N>     Return addresses the stack holds [default: 12, 16 past vip]
+  --addi-sets-vf        FX1E sets VF when I passes 0xFFF, as on the Amiga
+  --load-address <ADDR> Address the ROM is loaded and started at, 0x600 for ETI-660 [default: 0x200]
+  --font <SET|FILE>     vip, eti, dream, fish or an 80/240 byte font file [default: vip]
+  --font-address <ADDR> Address the font is loaded at [default: 0x000]
+  --scale <N>           Window pixels per CHIP-8 pixel [default: 16]
+  --palette <COLOURS>   Comma separated hex colours: background,plane1[,plane2,both]
+  --keymap <FILE>       Keymap config to load instead of the default layout
+  --state <FILE>        Save state to restore before running
+                        [quick saves go to ROM with a .state extension]
+  --rewind <N>          Snapshots kept for the rewind hotkey, 0 to disable [default: 300]
+  --rewind-interval <N> Frames between rewind snapshots [default: 6]
+  --wav <FILE>          Record the beeper to a WAV file instead of playing it
+  --beep-frequency <HZ> Pitch of the beeper [default: 440]
+  --beep-volume <V>     Loudness of the beeper from 0.0 to 1.0 [default: 0.25]
+  --beep-waveform <W>   square, triangle, sawtooth or sine [default: square]
+  --trace               Log every executed instruction
+  --step                Wait for the step hotkey before every instruction
+  --debug               Start in the interactive debugger on the terminal
+  --gdb <PORT>          Wait for a GDB remote debugger on localhost:PORT
+  --headless            Run without opening a window
+  --frames <N>          Stop after N frames, dumping the display (needs --headless)
+  --origin <ADDR>       Address the ROM is loaded at, for disasm [default: 0x200]
+  -o <FILE>             Where asm writes the ROM [default: SOURCE with a .ch8 extension]
+  -h, --help            Print this message";
+
+pub enum Command {
+    Help,
+    Run(Options),
+    Disasm { rom: PathBuf, origin: u16 },
+    Asm { source: PathBuf, output: PathBuf },
+}
+
+pub struct Options {
+    pub rom: PathBuf,
+    pub ips: u32,
+    pub quirks: Quirks,
+    // bytes of memory, XO-CHIP programs get the whole 16-bit address space
+    pub capacity: usize,
+    pub load_address: u16,
+    // a built-in font set or a font file, resolved when memory is set up
+    pub font: Option<String>,
+    pub font_address: u16,
+    pub scale: u32,
+    pub palette: Palette,
+    pub keymap: Option<PathBuf>,
+    pub state: Option<PathBuf>,
+    pub rewind_depth: usize,
+    pub rewind_interval: u32,
+    pub wav: Option<PathBuf>,
+    pub beeper: BeeperConfig,
+    pub trace: bool,
+    pub step: bool,
+    pub debug: bool,
+    pub gdb: Option<u16>,
+    pub headless: bool,
+    pub frames: Option<u64>,
+}
+
+pub fn parse<I: Iterator<Item = String>>(args: I) -> Result<Command, String> {
+    let mut args = args.peekable();
+    match args.peek().map(String::as_str) {
+        Some("disasm") => {
+            args.next();
+            return parse_disasm(args);
+        }
+        Some("asm") => {
+            args.next();
+            return parse_asm(args);
+        }
+        _ => {}
+    }
+    Ok(match parse_run(args)? {
+        Some(options) => Command::Run(options),
+        None => Command::Help,
+    })
+}
+
+fn parse_disasm<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut rom = None;
+    let mut origin = Memory::get_offset() as u16;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "--origin" => {
+                let value = args.next().ok_or("--origin Expects A Value")?;
+                origin = address(&arg, &value)?;
+            }
+            flag if flag.starts_with('-') => return Err(format!("Unknown Option `{}`", flag)),
+            path if rom.is_none() => rom = Some(PathBuf::from(path)),
+            path => return Err(format!("Unexpected Argument `{}`", path)),
+        }
+    }
+
+    Ok(Command::Disasm {
+        rom: rom.ok_or("No ROM Given")?,
+        origin,
+    })
+}
+
+fn parse_asm<I: Iterator<Item = String>>(mut args: I) -> Result<Command, String> {
+    let mut source = None;
+    let mut output = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-o" => output = Some(PathBuf::from(args.next().ok_or("-o Expects A Value")?)),
+            flag if flag.starts_with('-') => return Err(format!("Unknown Option `{}`", flag)),
+            path if source.is_none() => source = Some(PathBuf::from(path)),
+            path => return Err(format!("Unexpected Argument `{}`", path)),
+        }
+    }
+
+    let source = source.ok_or("No Source File Given")?;
+    let output = output.unwrap_or_else(|| source.with_extension("ch8"));
+    Ok(Command::Asm { source, output })
+}
+
+// Ok(None) means help was asked for
+fn parse_run<I: Iterator<Item = String>>(mut args: I) -> Result<Option<Options>, String> {
+    let mut rom = None;
+    // applied once the quirks preset, wherever it appears, is known
+    let mut stack_depth = None;
+    let mut addi_sets_vf = false;
+    let mut options = Options {
+        rom: PathBuf::new(),
+        ips: DEFAULT_IPS,
+        quirks: Quirks::default(),
+        capacity: Memory::new().get_cap(),
+        load_address: Memory::get_offset() as u16,
+        font: None,
+        font_address: font::DEFAULT_ADDRESS,
+        scale: 16,
+        palette: Palette::default(),
+        keymap: None,
+        state: None,
+        rewind_depth: rewind::DEFAULT_DEPTH,
+        rewind_interval: rewind::DEFAULT_INTERVAL,
+        wav: None,
+        beeper: BeeperConfig::default(),
+        trace: false,
+        step: false,
+        debug: false,
+        gdb: None,
+        headless: false,
+        frames: None,
+    };
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("{} Expects A Value", name))
+        };
+
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(None),
+            "--ips" => options.ips = number(&arg, &value(&arg)?)?,
+            "--quirks" => {
+                let preset = value(&arg)?;
+                options.quirks = Quirks::preset(&preset)
+                    .ok_or_else(|| format!("Unknown Quirks Preset `{}`", preset))?;
+                options.capacity = if options.quirks == Quirks::xo_chip() {
+                    Memory::get_xo_cap()
+                } else {
+                    Memory::new().get_cap()
+                };
+            }
+            "--stack-depth" => stack_depth = Some(number(&arg, &value(&arg)?)?),
+            "--addi-sets-vf" => addi_sets_vf = true,
+            "--load-address" => options.load_address = address(&arg, &value(&arg)?)?,
+            "--font" => options.font = Some(value(&arg)?),
+            "--font-address" => options.font_address = address(&arg, &value(&arg)?)?,
+            "--scale" => options.scale = number(&arg, &value(&arg)?)?,
+            "--palette" => options.palette = palette(&value(&arg)?)?,
+            "--keymap" => options.keymap = Some(PathBuf::from(value(&arg)?)),
+            "--state" => options.state = Some(PathBuf::from(value(&arg)?)),
+            "--rewind" => options.rewind_depth = number(&arg, &value(&arg)?)?,
+            "--rewind-interval" => options.rewind_interval = number(&arg, &value(&arg)?)?,
+            "--wav" => options.wav = Some(PathBuf::from(value(&arg)?)),
+            "--beep-frequency" => options.beeper.frequency = number(&arg, &value(&arg)?)?,
+            "--beep-volume" => options.beeper.volume = number(&arg, &value(&arg)?)?,
+            "--beep-waveform" => {
+                let name = value(&arg)?;
+                options.beeper.waveform = Waveform::from_name(&name)
+                    .ok_or_else(|| format!("Unknown Waveform `{}`", name))?;
+            }
+            "--trace" => options.trace = true,
+            "--step" => options.step = true,
+            "--debug" => options.debug = true,
+            "--gdb" => options.gdb = Some(number(&arg, &value(&arg)?)?),
+            "--headless" => options.headless = true,
+            "--frames" => options.frames = Some(number(&arg, &value(&arg)?)?),
+            flag if flag.starts_with('-') => return Err(format!("Unknown Option `{}`", flag)),
+            path => {
+                if rom.is_some() {
+                    return Err(format!("Unexpected Argument `{}`", path));
+                }
+                rom = Some(PathBuf::from(path));
+            }
+        }
+    }
+
+    options.rom = rom.ok_or("No ROM Given")?;
+    if let Some(depth) = stack_depth {
+        options.quirks.stack_depth = depth;
+    }
+    options.quirks.addi_sets_vf |= addi_sets_vf;
+    if options.ips == 0 || options.scale == 0 || options.quirks.stack_depth == 0 {
+        return Err("--ips, --scale And --stack-depth Must Be Greater Than Zero".to_string());
+    }
+    let frequency = options.beeper.frequency;
+    if !frequency.is_finite() || frequency <= 0.0 {
+        return Err("--beep-frequency Must Be Greater Than Zero".to_string());
+    }
+    if !(0.0..=1.0).contains(&options.beeper.volume) {
+        return Err("--beep-volume Must Be Between 0.0 And 1.0".to_string());
+    }
+    let debugging = options.debug || options.gdb.is_some();
+    if debugging && (options.step || options.frames.is_some()) {
+        return Err("--debug And --gdb Cannot Be Combined With --step Or --frames".to_string());
+    }
+    if options.debug && options.gdb.is_some() {
+        return Err("Choose One Of --debug And --gdb".to_string());
+    }
+    if options.frames.is_some() && !options.headless {
+        return Err("--frames Only Applies With --headless".to_string());
+    }
+
+    Ok(Some(options))
+}
+
+fn number<T: std::str::FromStr>(name: &str, value: &str) -> Result<T, String> {
+    value
+        .parse()
+        .map_err(|_| format!("{} Expects A Number, Found `{}`", name, value))
+}
+
+// hex with a 0x prefix, decimal otherwise
+fn address(name: &str, value: &str) -> Result<u16, String> {
+    let parsed = match value.strip_prefix("0x") {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => value.parse().ok(),
+    };
+    parsed.ok_or_else(|| format!("{} Expects An Address, Found `{}`", name, value))
+}
+
+fn palette(value: &str) -> Result<Palette, String> {
+    let mut palette = Palette::default();
+    let colours: Vec<&str> = value.split(',').collect();
+    if colours.len() != 2 && colours.len() != 4 {
+        return Err(format!(
+            "A Palette Needs 2 Or 4 Colours, Found {}",
+            colours.len()
+        ));
+    }
+
+    for (slot, colour) in palette.colors.iter_mut().zip(colours) {
+        let hex = colour.trim().trim_start_matches('#');
+        let rgb = u32::from_str_radix(hex, 16)
+            .ok()
+            .filter(|_| hex.len() == 6)
+            .ok_or_else(|| format!("`{}` Is Not A Hex Colour Like ff8800", colour))?;
+        *slot = ((rgb >> 16) as u8, (rgb >> 8) as u8, rgb as u8);
+    }
+
+    Ok(palette)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run_options(args: &[&str]) -> Result<Options, String> {
+        match parse(args.iter().map(|arg| arg.to_string()))? {
+            Command::Run(options) => Ok(options),
+            _ => panic!("{:?} did not parse as a run", args),
+        }
+    }
+
+    #[test]
+    fn stack_depth_overrides_the_preset() {
+        assert_eq!(run_options(&["rom.ch8"]).unwrap().quirks.stack_depth, 12);
+        let schip = run_options(&["--quirks", "schip", "rom.ch8"]).unwrap();
+        assert_eq!(schip.quirks.stack_depth, 16);
+
+        // whichever order the flags come in
+        for args in [
+            ["--quirks", "schip", "--stack-depth", "4", "rom.ch8"],
+            ["--stack-depth", "4", "--quirks", "schip", "rom.ch8"],
+        ] {
+            assert_eq!(run_options(&args).unwrap().quirks.stack_depth, 4);
+        }
+
+        assert!(run_options(&["--stack-depth", "0", "rom.ch8"]).is_err());
+    }
+
+    #[test]
+    fn beeper_flags_set_the_tone() {
+        assert_eq!(
+            run_options(&["rom.ch8"]).unwrap().beeper,
+            BeeperConfig::default()
+        );
+
+        let options = run_options(&[
+            "--beep-frequency",
+            "880",
+            "--beep-volume",
+            "0.5",
+            "--beep-waveform",
+            "Sine",
+            "rom.ch8",
+        ])
+        .unwrap();
+        assert_eq!(options.beeper.frequency, 880.0);
+        assert_eq!(options.beeper.volume, 0.5);
+        assert_eq!(options.beeper.waveform, Waveform::Sine);
+
+        for args in [
+            ["--beep-frequency", "0", "rom.ch8"],
+            ["--beep-frequency", "NaN", "rom.ch8"],
+            ["--beep-volume", "1.5", "rom.ch8"],
+            ["--beep-waveform", "noise", "rom.ch8"],
+        ] {
+            assert!(run_options(&args).is_err(), "{:?}", args);
+        }
+    }
+}
@@ -1,9 +1,14 @@
 use crate::{
     display::Display,
-    keyboard::{self, KeyBoardEvent, Keyboard},
+    error::Chip8Error,
+    keyboard::Keyboard,
     memory::Memory,
+    quirks::Quirks,
+    savestate::{self, StateReader, StateWriter},
+    stack::Stack,
 };
 use rand::Rng;
+use std::fmt;
 
 type NNN = u16;
 type NN = u8;
@@ -12,7 +17,7 @@ type X = u8;
 type Y = u8;
 
 #[allow(non_camel_case_types)]
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum OpCode {
     NONE,
     HALT,
@@ -65,53 +70,29 @@ pub enum OpCode {
 
     DUMP(X),
     LOAD(X),
-}
-
-pub struct CPU {
-    registers: [u8; 16],
-    i: u16,
-    #[allow(dead_code)]
-    sound: u8,
-    #[allow(dead_code)]
-    delay: u8,
 
-    pc: u16,
-    sp: u8,
+    // SUPER-CHIP 1.1
+    SCROLL_DOWN(N),
+    SCROLL_RIGHT,
+    SCROLL_LEFT,
+    EXIT,
+    LORES,
+    HIRES,
+    BIG_SPRI(X),
+    SAVE_FLAGS(X),
+    LOAD_FLAGS(X),
 
-    stack: [u16; 12],
-
-    latest_fetch: u16,
-    latest_addr: u16,
+    // XO-CHIP
+    LONG_I,
+    SAVE_RANGE(X, Y),
+    LOAD_RANGE(X, Y),
+    PLANE(N),
+    AUDIO,
+    PITCH(X),
 }
 
-impl CPU {
-    pub fn new() -> Self {
-        Self {
-            registers: [0; 16],
-            i: 0,
-            delay: 0,
-            sound: 0,
-
-            pc: Memory::get_offset() as u16,
-            sp: 0,
-            stack: [0; 12],
-
-            latest_fetch: 0,
-            latest_addr: 0,
-        }
-    }
-
-    pub fn fetch(&mut self, mem: &mut Memory) -> u16 {
-        let most_sig = mem[self.pc + 0];
-        let lest_sig = mem[self.pc + 1];
-        // self.log_value(" -- ", (most_sig as u16) << 8 | lest_sig as u16);
-
-        self.latest_fetch = (most_sig as u16) << 8 | lest_sig as u16;
-        self.latest_addr = self.pc;
-        self.latest_fetch
-    }
-
-    pub fn decode(&self, instruction: u16) -> OpCode {
+impl From<u16> for OpCode {
+    fn from(instruction: u16) -> Self {
         // the buffer actually only needs to store 16 bits (4bits * 4)
         // however for simplicity the array holds 32 bits (8bits * 4)
         // to make indexing and splitting easier
@@ -123,41 +104,47 @@ impl CPU {
         // we generate the mask by shifting a 1111 nibble 12 bytes for the first chunk, then 8 and so on
         // then we shift the masked value back so the number can be accurately cast as a u8
         buf[0] = ((instruction & 0b1111 << 12) >> 12) as u8;
-        buf[1] = ((instruction & 0b1111 << 08) >> 08) as u8;
-        buf[2] = ((instruction & 0b1111 << 04) >> 04) as u8;
-        buf[3] = ((instruction & 0b1111 << 00) >> 00) as u8;
+        buf[1] = ((instruction & 0b1111 << 8) >> 8) as u8;
+        buf[2] = ((instruction & 0b1111 << 4) >> 4) as u8;
+        buf[3] = (instruction & 0b1111) as u8;
 
         fn tripple(buf: [u8; 3]) -> u16 {
             let mut output: u16 = 0;
             output += (buf[0] as u16) << 8;
             output += (buf[1] as u16) << 4;
-            output += (buf[2] as u16) << 0;
+            output += buf[2] as u16;
             output
         }
 
         fn double(buf: [u8; 2]) -> u8 {
             let mut output: u8 = 0;
-            output += (buf[0] << 4) as u8;
-            output += (buf[1] << 0) as u8;
+            output += buf[0] << 4;
+            output += buf[1];
             output
         }
 
         fn single(buf: u8) -> u8 {
-            let mut output: u8 = 0;
-            output += (buf << 0) as u8;
-            output
+            buf
         }
 
         match buf {
             [0x0, 0x0, 0x0, 0x0] => OpCode::HALT,
             [0x0, 0x0, 0xE, 0x0] => OpCode::CLEAR,
             [0x0, 0x0, 0xE, 0xE] => OpCode::RETURN,
+            [0x0, 0x0, 0xC, n] => OpCode::SCROLL_DOWN(single(n)),
+            [0x0, 0x0, 0xF, 0xB] => OpCode::SCROLL_RIGHT,
+            [0x0, 0x0, 0xF, 0xC] => OpCode::SCROLL_LEFT,
+            [0x0, 0x0, 0xF, 0xD] => OpCode::EXIT,
+            [0x0, 0x0, 0xF, 0xE] => OpCode::LORES,
+            [0x0, 0x0, 0xF, 0xF] => OpCode::HIRES,
             [0x0, nnn @ ..] => OpCode::ROUTINE(tripple(nnn)),
             [0x1, nnn @ ..] => OpCode::GOTO(tripple(nnn)),
             [0x2, nnn @ ..] => OpCode::CALL(tripple(nnn)),
             [0x3, x, nn @ ..] => OpCode::EQ(single(x), double(nn)),
             [0x4, x, nn @ ..] => OpCode::NEQ(single(x), double(nn)),
             [0x5, x, y, 0x0] => OpCode::EQ_REG(single(x), single(y)),
+            [0x5, x, y, 0x2] => OpCode::SAVE_RANGE(single(x), single(y)),
+            [0x5, x, y, 0x3] => OpCode::LOAD_RANGE(single(x), single(y)),
             [0x6, x, nn @ ..] => OpCode::SET_CONST(single(x), double(nn)),
             [0x7, x, nn @ ..] => OpCode::ADD_CONST(single(x), double(nn)),
             [0x8, x, y, 0x0] => OpCode::SET_REG(single(x), single(y)),
@@ -176,18 +163,348 @@ impl CPU {
             [0xD, x, y, n] => OpCode::DRAW(single(x), single(y), single(n)),
             [0xE, x, 0x9, 0xE] => OpCode::KEY_P(single(x)),
             [0xE, x, 0xA, 0x1] => OpCode::KEY_NP(single(x)),
+            [0xF, 0x0, 0x0, 0x0] => OpCode::LONG_I,
+            [0xF, n, 0x0, 0x1] => OpCode::PLANE(single(n)),
+            [0xF, 0x0, 0x0, 0x2] => OpCode::AUDIO,
             [0xF, x, 0x0, 0x7] => OpCode::GET_DELAY(single(x)),
             [0xF, x, 0x0, 0xA] => OpCode::GET_KEY(single(x)),
             [0xF, x, 0x1, 0x5] => OpCode::SET_DELAY(single(x)),
             [0xF, x, 0x1, 0x8] => OpCode::SET_SOUND(single(x)),
             [0xF, x, 0x1, 0xE] => OpCode::ADDI(single(x)),
             [0xF, x, 0x2, 0x9] => OpCode::SPRI(single(x)),
+            [0xF, x, 0x3, 0x0] => OpCode::BIG_SPRI(single(x)),
+            [0xF, x, 0x3, 0xA] => OpCode::PITCH(single(x)),
             [0xF, x, 0x3, 0x3] => OpCode::BCP(single(x)),
             [0xF, x, 0x5, 0x5] => OpCode::DUMP(single(x)),
             [0xF, x, 0x6, 0x5] => OpCode::LOAD(single(x)),
+            [0xF, x, 0x7, 0x5] => OpCode::SAVE_FLAGS(single(x)),
+            [0xF, x, 0x8, 0x5] => OpCode::LOAD_FLAGS(single(x)),
             _ => OpCode::NONE,
         }
     }
+}
+
+// The inverse of decoding. LONG_I is only the F000 half, its address is
+// the word that follows, and NONE has no encoding so it takes FFFF, which
+// decodes back to NONE.
+impl From<OpCode> for u16 {
+    fn from(op: OpCode) -> Self {
+        let xy =
+            |op: u16, x: u8, y: u8, n: u16| op | (x as u16 & 0xF) << 8 | (y as u16 & 0xF) << 4 | n;
+        let xnn = |op: u16, x: u8, nn: u8| op | (x as u16 & 0xF) << 8 | nn as u16;
+        let x = |op: u16, x: u8| op | (x as u16 & 0xF) << 8;
+
+        match op {
+            OpCode::NONE => 0xFFFF,
+            OpCode::HALT => 0x0000,
+            OpCode::ROUTINE(addr) => addr & 0xFFF,
+            OpCode::CLEAR => 0x00E0,
+            OpCode::RETURN => 0x00EE,
+            OpCode::GOTO(addr) => 0x1000 | addr & 0xFFF,
+            OpCode::CALL(addr) => 0x2000 | addr & 0xFFF,
+            OpCode::EQ(vx, nn) => xnn(0x3000, vx, nn),
+            OpCode::NEQ(vx, nn) => xnn(0x4000, vx, nn),
+            OpCode::EQ_REG(vx, vy) => xy(0x5000, vx, vy, 0x0),
+            OpCode::SET_CONST(vx, nn) => xnn(0x6000, vx, nn),
+            OpCode::ADD_CONST(vx, nn) => xnn(0x7000, vx, nn),
+            OpCode::SET_REG(vx, vy) => xy(0x8000, vx, vy, 0x0),
+            OpCode::OR(vx, vy) => xy(0x8000, vx, vy, 0x1),
+            OpCode::AND(vx, vy) => xy(0x8000, vx, vy, 0x2),
+            OpCode::XOR(vx, vy) => xy(0x8000, vx, vy, 0x3),
+            OpCode::ADD_REG(vx, vy) => xy(0x8000, vx, vy, 0x4),
+            OpCode::SUB_REG(vx, vy) => xy(0x8000, vx, vy, 0x5),
+            OpCode::BSHIFT_RGHT(vx, vy) => xy(0x8000, vx, vy, 0x6),
+            OpCode::SUBTRACT(vx, vy) => xy(0x8000, vx, vy, 0x7),
+            OpCode::BSHIFT_LEFT(vx, vy) => xy(0x8000, vx, vy, 0xE),
+            OpCode::NEQ_REG(vx, vy) => xy(0x9000, vx, vy, 0x0),
+            OpCode::SETI(addr) => 0xA000 | addr & 0xFFF,
+            OpCode::JUMP(addr) => 0xB000 | addr & 0xFFF,
+            OpCode::RAND(vx, nn) => xnn(0xC000, vx, nn),
+            OpCode::DRAW(vx, vy, n) => xy(0xD000, vx, vy, n as u16 & 0xF),
+            OpCode::KEY_P(vx) => x(0xE09E, vx),
+            OpCode::KEY_NP(vx) => x(0xE0A1, vx),
+            OpCode::GET_DELAY(vx) => x(0xF007, vx),
+            OpCode::GET_KEY(vx) => x(0xF00A, vx),
+            OpCode::SET_DELAY(vx) => x(0xF015, vx),
+            OpCode::SET_SOUND(vx) => x(0xF018, vx),
+            OpCode::ADDI(vx) => x(0xF01E, vx),
+            OpCode::SPRI(vx) => x(0xF029, vx),
+            OpCode::BCP(vx) => x(0xF033, vx),
+            OpCode::DUMP(vx) => x(0xF055, vx),
+            OpCode::LOAD(vx) => x(0xF065, vx),
+            OpCode::SCROLL_DOWN(n) => 0x00C0 | n as u16 & 0xF,
+            OpCode::SCROLL_RIGHT => 0x00FB,
+            OpCode::SCROLL_LEFT => 0x00FC,
+            OpCode::EXIT => 0x00FD,
+            OpCode::LORES => 0x00FE,
+            OpCode::HIRES => 0x00FF,
+            OpCode::BIG_SPRI(vx) => x(0xF030, vx),
+            OpCode::SAVE_FLAGS(vx) => x(0xF075, vx),
+            OpCode::LOAD_FLAGS(vx) => x(0xF085, vx),
+            OpCode::LONG_I => 0xF000,
+            OpCode::SAVE_RANGE(vx, vy) => xy(0x5000, vx, vy, 0x2),
+            OpCode::LOAD_RANGE(vx, vy) => xy(0x5000, vx, vy, 0x3),
+            OpCode::PLANE(n) => x(0xF001, n),
+            OpCode::AUDIO => 0xF002,
+            OpCode::PITCH(vx) => x(0xF03A, vx),
+        }
+    }
+}
+
+// Mnemonics in the style of Cowgod's reference, with the SUPER-CHIP and
+// XO-CHIP additions named after their Octo counterparts
+impl fmt::Display for OpCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            OpCode::NONE => write!(f, "???"),
+            OpCode::HALT => write!(f, "SYS 0x000"),
+            OpCode::ROUTINE(addr) => write!(f, "SYS {:#05X}", addr),
+            OpCode::CLEAR => write!(f, "CLS"),
+            OpCode::RETURN => write!(f, "RET"),
+            OpCode::GOTO(addr) => write!(f, "JP {:#05X}", addr),
+            OpCode::CALL(addr) => write!(f, "CALL {:#05X}", addr),
+            OpCode::EQ(x, nn) => write!(f, "SE V{:X}, {:#04X}", x, nn),
+            OpCode::NEQ(x, nn) => write!(f, "SNE V{:X}, {:#04X}", x, nn),
+            OpCode::EQ_REG(x, y) => write!(f, "SE V{:X}, V{:X}", x, y),
+            OpCode::SET_CONST(x, nn) => write!(f, "LD V{:X}, {:#04X}", x, nn),
+            OpCode::ADD_CONST(x, nn) => write!(f, "ADD V{:X}, {:#04X}", x, nn),
+            OpCode::OR(x, y) => write!(f, "OR V{:X}, V{:X}", x, y),
+            OpCode::AND(x, y) => write!(f, "AND V{:X}, V{:X}", x, y),
+            OpCode::XOR(x, y) => write!(f, "XOR V{:X}, V{:X}", x, y),
+            OpCode::SET_REG(x, y) => write!(f, "LD V{:X}, V{:X}", x, y),
+            OpCode::ADD_REG(x, y) => write!(f, "ADD V{:X}, V{:X}", x, y),
+            OpCode::SUB_REG(x, y) => write!(f, "SUB V{:X}, V{:X}", x, y),
+            OpCode::BSHIFT_RGHT(x, y) => write!(f, "SHR V{:X}, V{:X}", x, y),
+            OpCode::BSHIFT_LEFT(x, y) => write!(f, "SHL V{:X}, V{:X}", x, y),
+            OpCode::SUBTRACT(x, y) => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            OpCode::NEQ_REG(x, y) => write!(f, "SNE V{:X}, V{:X}", x, y),
+            OpCode::SETI(addr) => write!(f, "LD I, {:#05X}", addr),
+            OpCode::JUMP(addr) => write!(f, "JP V0, {:#05X}", addr),
+            OpCode::RAND(x, nn) => write!(f, "RND V{:X}, {:#04X}", x, nn),
+            OpCode::DRAW(x, y, n) => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            OpCode::KEY_P(x) => write!(f, "SKP V{:X}", x),
+            OpCode::KEY_NP(x) => write!(f, "SKNP V{:X}", x),
+            OpCode::GET_DELAY(x) => write!(f, "LD V{:X}, DT", x),
+            OpCode::GET_KEY(x) => write!(f, "LD V{:X}, K", x),
+            OpCode::SET_DELAY(x) => write!(f, "LD DT, V{:X}", x),
+            OpCode::SET_SOUND(x) => write!(f, "LD ST, V{:X}", x),
+            OpCode::ADDI(x) => write!(f, "ADD I, V{:X}", x),
+            OpCode::SPRI(x) => write!(f, "LD F, V{:X}", x),
+            OpCode::BCP(x) => write!(f, "LD B, V{:X}", x),
+            OpCode::DUMP(x) => write!(f, "LD [I], V{:X}", x),
+            OpCode::LOAD(x) => write!(f, "LD V{:X}, [I]", x),
+            OpCode::SCROLL_DOWN(n) => write!(f, "SCD {}", n),
+            OpCode::SCROLL_RIGHT => write!(f, "SCR"),
+            OpCode::SCROLL_LEFT => write!(f, "SCL"),
+            OpCode::EXIT => write!(f, "EXIT"),
+            OpCode::LORES => write!(f, "LOW"),
+            OpCode::HIRES => write!(f, "HIGH"),
+            OpCode::BIG_SPRI(x) => write!(f, "LD HF, V{:X}", x),
+            OpCode::SAVE_FLAGS(x) => write!(f, "LD R, V{:X}", x),
+            OpCode::LOAD_FLAGS(x) => write!(f, "LD V{:X}, R", x),
+            // the address is the word after F000, which only a disassembler can see
+            OpCode::LONG_I => write!(f, "LD I, LONG"),
+            OpCode::SAVE_RANGE(x, y) => write!(f, "SAVE V{:X}, V{:X}", x, y),
+            OpCode::LOAD_RANGE(x, y) => write!(f, "LOAD V{:X}, V{:X}", x, y),
+            OpCode::PLANE(n) => write!(f, "PLANE {}", n),
+            OpCode::AUDIO => write!(f, "AUDIO"),
+            OpCode::PITCH(x) => write!(f, "PITCH V{:X}", x),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct CPU {
+    registers: [u8; 16],
+    i: u16,
+    sound: u8,
+    delay: u8,
+
+    pc: u16,
+    stack: Stack,
+
+    // SUPER-CHIP persistent "RPL user flags"
+    flags: [u8; 8],
+
+    // XO-CHIP audio pattern buffer and playback pitch
+    pattern: Option<[u8; 16]>,
+    pitch: u8,
+
+    latest_fetch: u16,
+    latest_addr: u16,
+
+    quirks: Quirks,
+    // set at every vertical blank, consumed by DRAW when the display wait quirk is on
+    vblank: bool,
+    // FX0A is parked until a key is released
+    key_wait: bool,
+}
+
+impl CPU {
+    pub fn new() -> Self {
+        Self::with_quirks(Quirks::default())
+    }
+
+    pub fn with_quirks(quirks: Quirks) -> Self {
+        Self {
+            registers: [0; 16],
+            i: 0,
+            delay: 0,
+            sound: 0,
+
+            pc: Memory::get_offset() as u16,
+            stack: Stack::new(quirks.stack_depth),
+
+            flags: [0; 8],
+
+            pattern: None,
+            pitch: 64,
+
+            latest_fetch: 0,
+            latest_addr: 0,
+
+            quirks,
+            vblank: false,
+            key_wait: false,
+        }
+    }
+
+    pub fn get_quirks(&self) -> &Quirks {
+        &self.quirks
+    }
+
+    pub fn get_registers(&self) -> &[u8; 16] {
+        &self.registers
+    }
+
+    pub fn get_i(&self) -> u16 {
+        self.i
+    }
+
+    pub fn get_pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn get_sp(&self) -> u8 {
+        self.stack.len() as u8
+    }
+
+    pub fn get_stack(&self) -> &Stack {
+        &self.stack
+    }
+
+    pub fn get_delay(&self) -> u8 {
+        self.delay
+    }
+
+    pub fn get_sound(&self) -> u8 {
+        self.sound
+    }
+
+    pub fn set_register(&mut self, idx: usize, value: u8) {
+        self.registers[idx & 0xF] = value;
+    }
+
+    pub fn set_i(&mut self, i: u16) {
+        self.i = i;
+    }
+
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    pub fn set_delay(&mut self, delay: u8) {
+        self.delay = delay;
+    }
+
+    pub fn set_sound(&mut self, sound: u8) {
+        self.sound = sound;
+    }
+
+    // quirks describe the platform rather than the program, so they are not saved
+    pub(crate) fn write_state(&self, state: &mut StateWriter) {
+        state.bytes(&self.registers);
+        state.u16(self.i);
+        state.u8(self.sound);
+        state.u8(self.delay);
+        state.u16(self.pc);
+        state.u32(self.stack.len() as u32);
+        for address in self.stack.addresses() {
+            state.u16(*address);
+        }
+        state.bytes(&self.flags);
+        state.bool(self.pattern.is_some());
+        state.bytes(&self.pattern.unwrap_or_default());
+        state.u8(self.pitch);
+        state.u16(self.latest_fetch);
+        state.u16(self.latest_addr);
+        state.bool(self.vblank);
+        state.bool(self.key_wait);
+    }
+
+    pub(crate) fn read_state(&mut self, state: &mut StateReader) -> Result<(), Chip8Error> {
+        state.array(&mut self.registers)?;
+        self.i = state.u16()?;
+        self.sound = state.u8()?;
+        self.delay = state.u8()?;
+        self.pc = state.u16()?;
+        let len = state.u32()?;
+        let addresses = (0..len).map(|_| state.u16()).collect::<Result<_, _>>()?;
+        if !self.stack.set_addresses(addresses) {
+            return Err(savestate::invalid("Stack Is Deeper Than The Quirks Allow"));
+        }
+        state.array(&mut self.flags)?;
+        let has_pattern = state.bool()?;
+        let mut pattern = [0; 16];
+        state.array(&mut pattern)?;
+        self.pattern = has_pattern.then_some(pattern);
+        self.pitch = state.u8()?;
+        self.latest_fetch = state.u16()?;
+        self.latest_addr = state.u16()?;
+        self.vblank = state.bool()?;
+        self.key_wait = state.bool()?;
+        Ok(())
+    }
+
+    pub fn fetch(&mut self, mem: &mut Memory) -> Result<u16, Chip8Error> {
+        self.latest_addr = self.pc;
+        let most_sig = self.read(mem, self.pc as usize)?;
+        let lest_sig = self.read(mem, self.pc as usize + 1)?;
+
+        self.latest_fetch = (most_sig as u16) << 8 | lest_sig as u16;
+        Ok(self.latest_fetch)
+    }
+
+    fn read(&self, mem: &Memory, address: usize) -> Result<u8, Chip8Error> {
+        mem.get(address).ok_or(Chip8Error::OutOfBounds {
+            address,
+            pc: self.latest_addr,
+        })
+    }
+
+    fn write(&self, mem: &mut Memory, address: usize, value: u8) -> Result<(), Chip8Error> {
+        let pc = self.latest_addr;
+        *mem.get_mut(address)
+            .ok_or(Chip8Error::OutOfBounds { address, pc })? = value;
+        Ok(())
+    }
+
+    // faults before a transfer of len bytes at I touches anything,
+    // so an instruction running off the end of memory has no effect
+    fn check_span(&self, mem: &Memory, len: usize) -> Result<(), Chip8Error> {
+        let last = self.i as usize + len - 1;
+        if last >= mem.get_cap() {
+            return Err(Chip8Error::OutOfBounds {
+                address: last,
+                pc: self.latest_addr,
+            });
+        }
+        Ok(())
+    }
+
+    pub fn decode(&self, instruction: u16) -> OpCode {
+        OpCode::from(instruction)
+    }
 
     pub fn execute(
         &mut self,
@@ -195,26 +512,37 @@ impl CPU {
         display: &mut Display,
         keyboard: &mut Keyboard,
         opcode: &OpCode,
-    ) -> Result<(), String> {
+    ) -> Result<(), Chip8Error> {
         let mut inc = true;
 
         match opcode {
-            OpCode::NONE => {}
+            OpCode::NONE => {
+                return Err(Chip8Error::UnknownOpcode {
+                    opcode: self.latest_fetch,
+                    pc: self.latest_addr,
+                })
+            }
             OpCode::HALT => {}
-            OpCode::ROUTINE(_) => {
-                self.log_last();
-                todo!();
+            OpCode::ROUTINE(addr) => {
+                return Err(Chip8Error::UnimplementedRoutine {
+                    address: *addr,
+                    pc: self.latest_addr,
+                })
             }
             OpCode::CLEAR => display.clear_dispaly(),
             OpCode::CALL(addr) => {
-                self.stack[self.sp as usize] = self.pc;
-                self.sp += 1;
+                if !self.stack.push(self.pc) {
+                    return Err(Chip8Error::StackOverflow {
+                        pc: self.latest_addr,
+                    });
+                }
                 self.pc = *addr;
                 inc = false;
             }
             OpCode::RETURN => {
-                self.sp -= 1;
-                self.pc = self.stack[self.sp as usize];
+                self.pc = self.stack.pop().ok_or(Chip8Error::StackUnderflow {
+                    pc: self.latest_addr,
+                })?;
                 inc = true;
             }
             OpCode::GOTO(addr) => {
@@ -224,143 +552,305 @@ impl CPU {
 
             OpCode::EQ(reg, val) => {
                 if self.registers[*reg as usize] == *val {
-                    println!("LOL");
-                    self.pc += 2;
+                    self.skip(mem);
                 }
             }
             OpCode::NEQ(reg, val) => {
                 if self.registers[*reg as usize] != *val {
-                    self.pc += 2;
+                    self.skip(mem);
                 }
             }
             OpCode::EQ_REG(rega, regb) => {
                 if self.registers[*rega as usize] == self.registers[*regb as usize] {
-                    self.pc += 2;
+                    self.skip(mem);
                 }
             }
             OpCode::SET_CONST(reg, val) => {
                 self.registers[*reg as usize] = *val;
             }
             OpCode::ADD_CONST(reg, val) => {
-                self.registers[*reg as usize] += *val;
+                // 7XNN never touches the carry flag
+                self.registers[*reg as usize] = self.registers[*reg as usize].wrapping_add(*val);
             }
             OpCode::OR(rega, regb) => {
-                self.registers[*rega as usize] =
-                    self.registers[*rega as usize] | self.registers[*regb as usize]
+                self.registers[*rega as usize] |= self.registers[*regb as usize];
+                if self.quirks.vf_reset {
+                    self.registers[15] = 0;
+                }
             }
             OpCode::AND(rega, regb) => {
-                self.registers[*rega as usize] =
-                    self.registers[*rega as usize] & self.registers[*regb as usize]
+                self.registers[*rega as usize] &= self.registers[*regb as usize];
+                if self.quirks.vf_reset {
+                    self.registers[15] = 0;
+                }
             }
             OpCode::XOR(rega, regb) => {
-                self.registers[*rega as usize] =
-                    self.registers[*rega as usize] ^ self.registers[*regb as usize]
+                self.registers[*rega as usize] ^= self.registers[*regb as usize];
+                if self.quirks.vf_reset {
+                    self.registers[15] = 0;
+                }
             }
             OpCode::SET_REG(rega, regb) => {
                 self.registers[*rega as usize] = self.registers[*regb as usize]
             }
+            // for the whole 8XYN group the flag is written after the result,
+            // so when X is F the flag is what ends up in VF
             OpCode::ADD_REG(rega, regb) => {
-                self.registers[*rega as usize] =
-                    self.registers[*rega as usize] + self.registers[*regb as usize]
+                let (result, carry) =
+                    self.registers[*rega as usize].overflowing_add(self.registers[*regb as usize]);
+                self.registers[*rega as usize] = result;
+                self.registers[15] = carry as u8;
             }
             OpCode::SUB_REG(rega, regb) => {
-                self.registers[*rega as usize] =
-                    self.registers[*rega as usize] - self.registers[*regb as usize]
+                let (result, borrow) =
+                    self.registers[*rega as usize].overflowing_sub(self.registers[*regb as usize]);
+                self.registers[*rega as usize] = result;
+                self.registers[15] = !borrow as u8;
             }
-            OpCode::BSHIFT_RGHT(rega, _regb) => {
-                self.registers[15] = self.registers[*rega as usize] >> 7;
-                self.registers[*rega as usize] = self.registers[*rega as usize] >> 1
+            OpCode::BSHIFT_RGHT(rega, regb) => {
+                let value = self.shift_source(*rega, *regb);
+                self.registers[*rega as usize] = value >> 1;
+                self.registers[15] = value & 0b1;
             }
-            OpCode::BSHIFT_LEFT(rega, _regb) => {
-                self.registers[15] = self.registers[*rega as usize] & 0b1;
-                self.registers[*rega as usize] = self.registers[*rega as usize] << 1
+            OpCode::BSHIFT_LEFT(rega, regb) => {
+                let value = self.shift_source(*rega, *regb);
+                self.registers[*rega as usize] = value << 1;
+                self.registers[15] = value >> 7;
             }
             OpCode::SUBTRACT(rega, regb) => {
-                self.registers[*rega as usize] =
-                    self.registers[*regb as usize] - self.registers[*rega as usize];
+                let (result, borrow) =
+                    self.registers[*regb as usize].overflowing_sub(self.registers[*rega as usize]);
+                self.registers[*rega as usize] = result;
+                self.registers[15] = !borrow as u8;
             }
             OpCode::NEQ_REG(rega, regb) => {
                 if self.registers[*rega as usize] != self.registers[*regb as usize] {
-                    self.pc += 2;
+                    self.skip(mem);
                 }
             }
             OpCode::SETI(addr) => {
                 self.i = *addr;
             }
             OpCode::JUMP(addr) => {
-                self.pc = self.registers[0] as u16 + *addr;
+                let reg = if self.quirks.jump_uses_vx {
+                    (*addr >> 8) as usize
+                } else {
+                    0
+                };
+                self.pc = self.registers[reg] as u16 + *addr;
                 inc = false;
             }
             OpCode::RAND(reg, val) => {
                 let num = rand::thread_rng().gen_range(0..255);
                 self.registers[*reg as usize] = num & *val;
             }
+            OpCode::DRAW(_, _, _) if self.quirks.display_wait && !self.vblank => {
+                // hold on this instruction until the next vertical blank
+                inc = false;
+            }
             OpCode::DRAW(rega, regb, n) => {
-                let x = self.registers[*rega as usize];
-                let y = self.registers[*regb as usize];
-                for i in 0..*n {
-                    let bits: u8 = mem[self.i + i as u16];
-                    // println!("BITS: {:#010b}", bits);
-                    for j in 0..8 {
-                        let bit = (bits >> (8 - j)) & 0b1;
-                        if display.draw_suqare(x + j, y + i, bit == 1) {
-                            self.registers[15] = 1;
+                self.vblank = false;
+                let (width, height) = display.size();
+                // the start position always wraps onto the screen, it is
+                // only the pixels running off the edge that clip or wrap
+                let x = self.registers[*rega as usize] as u32 % width;
+                let y = self.registers[*regb as usize] as u32 % height;
+                // DXY0 draws a 16x16 SUPER-CHIP sprite, two bytes per row
+                let (rows, cols) = if *n == 0 { (16, 16) } else { (*n as u32, 8) };
+                let mut erased = false;
+                // with both XO-CHIP planes selected, the second plane's
+                // sprite data follows straight after the first's
+                let mut addr = self.i as usize;
+                for plane in [0b01, 0b10] {
+                    if display.get_planes() & plane == 0 {
+                        continue;
+                    }
+                    for i in 0..rows {
+                        let bits: u16 = if cols == 16 {
+                            let row = (self.read(mem, addr)? as u16) << 8
+                                | self.read(mem, addr + 1)? as u16;
+                            addr += 2;
+                            row
+                        } else {
+                            addr += 1;
+                            self.read(mem, addr - 1)? as u16
+                        };
+                        for j in 0..cols {
+                            // the most significant bit is the leftmost pixel
+                            let bit = (bits >> (cols - 1 - j)) & 0b1;
+                            let (px, py) = (x + j, y + i);
+                            if self.quirks.clip_sprites && (px >= width || py >= height) {
+                                continue;
+                            }
+                            if display.draw_suqare(px % width, py % height, plane, bit == 1) {
+                                erased = true;
+                            }
                         }
                     }
                 }
+                // VF is set only when a lit pixel was turned off
+                self.registers[15] = erased as u8;
             }
             OpCode::KEY_P(reg) => {
                 if keyboard.is_key_pressed(self.registers[*reg as usize]) {
-                    self.pc += 2;
+                    self.skip(mem);
                 }
             }
             OpCode::KEY_NP(reg) => {
                 if !keyboard.is_key_pressed(self.registers[*reg as usize]) {
-                    self.pc += 2;
+                    self.skip(mem);
                 }
             }
-            OpCode::GET_KEY(reg) => loop {
-                if let KeyBoardEvent::KeyPressed(n) = keyboard.wait_key(display) {
+            OpCode::GET_KEY(reg) => {
+                // rather than blocking on the keyboard, FX0A re-executes every
+                // cycle until a key goes up, as the COSMAC VIP waited for the
+                // release rather than the press
+                if !self.key_wait {
+                    self.key_wait = true;
+                    keyboard.clear_released();
+                    inc = false;
+                } else if let Some(n) = keyboard.released_key() {
                     self.registers[*reg as usize] = n;
-                    break;
+                    self.key_wait = false;
+                    keyboard.clear_released();
+                } else {
+                    inc = false;
                 }
-            },
+            }
             OpCode::GET_DELAY(reg) => {
                 self.registers[*reg as usize] = self.delay;
             }
             OpCode::SET_DELAY(reg) => self.delay = self.registers[*reg as usize],
             OpCode::SET_SOUND(reg) => self.sound = self.registers[*reg as usize],
             OpCode::ADDI(reg) => {
-                self.i += self.registers[*reg as usize] as u16;
+                self.i = self.i.wrapping_add(self.registers[*reg as usize] as u16);
+                if self.quirks.addi_sets_vf {
+                    self.registers[15] = (self.i > 0xFFF) as u8;
+                }
             }
             OpCode::SPRI(reg) => {
-                let char = self.registers[*reg as usize];
-                self.i = char as u16 * 5 as u16;
+                self.i = mem.get_font().small_glyph(self.registers[*reg as usize]);
             }
             OpCode::BCP(reg) => {
                 let decimal = self.registers[*reg as usize];
-                mem[self.i] = decimal / 100;
-                mem[self.i + 1] = (decimal % 100) / 10;
-                mem[self.i + 2] = decimal % 10;
+                self.check_span(mem, 3)?;
+                self.write(mem, self.i as usize, decimal / 100)?;
+                self.write(mem, self.i as usize + 1, (decimal % 100) / 10)?;
+                self.write(mem, self.i as usize + 2, decimal % 10)?;
             }
+            // V0 through VX, VX included
             OpCode::DUMP(idx) => {
-                for i in 0..*idx {
-                    mem[self.i + i as u16] = self.registers[i as usize];
+                let count = *idx as usize + 1;
+                self.check_span(mem, count)?;
+                for i in 0..count {
+                    self.write(mem, self.i as usize + i, self.registers[i])?;
+                }
+                if self.quirks.load_store_increments_i {
+                    self.i = self.i.wrapping_add(count as u16);
                 }
             }
             OpCode::LOAD(idx) => {
-                for i in 0..*idx {
-                    self.registers[i as usize] = mem[self.i + i as u16];
+                let count = *idx as usize + 1;
+                self.check_span(mem, count)?;
+                for i in 0..count {
+                    self.registers[i] = self.read(mem, self.i as usize + i)?;
+                }
+                if self.quirks.load_store_increments_i {
+                    self.i = self.i.wrapping_add(count as u16);
+                }
+            }
+            OpCode::SCROLL_DOWN(n) => display.scroll_down(*n as u32),
+            OpCode::SCROLL_RIGHT => display.scroll_right(4),
+            OpCode::SCROLL_LEFT => display.scroll_left(4),
+            OpCode::EXIT => {
+                return Err(Chip8Error::Exit {
+                    pc: self.latest_addr,
+                })
+            }
+            OpCode::LORES => display.set_hires(false),
+            OpCode::HIRES => display.set_hires(true),
+            OpCode::BIG_SPRI(reg) => {
+                self.i = mem.get_font().big_glyph(self.registers[*reg as usize]);
+            }
+            OpCode::SAVE_FLAGS(idx) => {
+                let count = (*idx as usize + 1).min(self.flags.len());
+                self.flags[..count].copy_from_slice(&self.registers[..count]);
+            }
+            OpCode::LOAD_FLAGS(idx) => {
+                let count = (*idx as usize + 1).min(self.flags.len());
+                self.registers[..count].copy_from_slice(&self.flags[..count]);
+            }
+            OpCode::LONG_I => {
+                // F000 NNNN, the address is the word following the instruction
+                let most_sig = self.read(mem, self.pc as usize + 2)?;
+                let lest_sig = self.read(mem, self.pc as usize + 3)?;
+                self.i = (most_sig as u16) << 8 | lest_sig as u16;
+                self.pc = self.pc.wrapping_add(2);
+            }
+            OpCode::SAVE_RANGE(rega, regb) => {
+                self.check_span(mem, Self::range(*rega, *regb).count())?;
+                for (offset, reg) in Self::range(*rega, *regb).enumerate() {
+                    self.write(mem, self.i as usize + offset, self.registers[reg])?;
+                }
+            }
+            OpCode::LOAD_RANGE(rega, regb) => {
+                self.check_span(mem, Self::range(*rega, *regb).count())?;
+                for (offset, reg) in Self::range(*rega, *regb).enumerate() {
+                    self.registers[reg] = self.read(mem, self.i as usize + offset)?;
+                }
+            }
+            OpCode::PLANE(n) => display.set_planes(*n),
+            OpCode::AUDIO => {
+                let mut pattern = [0; 16];
+                for (offset, byte) in pattern.iter_mut().enumerate() {
+                    *byte = self.read(mem, self.i as usize + offset)?;
                 }
+                self.pattern = Some(pattern);
             }
+            OpCode::PITCH(reg) => self.pitch = self.registers[*reg as usize],
         }
 
+        // 64 KiB of XO-CHIP memory fills the whole address space, so the
+        // program counter wraps back round to 0
         if inc {
-            self.pc += 2
+            self.pc = self.pc.wrapping_add(2)
         };
         Ok(())
     }
 
+    // skips the next instruction, which is twice as long if it is F000 NNNN
+    fn skip(&mut self, mem: &Memory) {
+        let next = self.pc.wrapping_add(2) as usize;
+        let long = mem.get(next) == Some(0xF0) && mem.get(next + 1) == Some(0x00);
+        self.pc = self.pc.wrapping_add(if long { 4 } else { 2 });
+    }
+
+    // registers X through Y, walking backwards when Y < X
+    fn range(rega: X, regb: Y) -> Box<dyn Iterator<Item = usize>> {
+        let (rega, regb) = (rega as usize, regb as usize);
+        if rega <= regb {
+            Box::new(rega..=regb)
+        } else {
+            Box::new((regb..=rega).rev())
+        }
+    }
+
+    fn shift_source(&self, rega: X, regb: Y) -> u8 {
+        if self.quirks.shift_uses_vy {
+            self.registers[regb as usize]
+        } else {
+            self.registers[rega as usize]
+        }
+    }
+
+    pub fn is_waiting_for_key(&self) -> bool {
+        self.key_wait
+    }
+
+    pub fn vblank(&mut self) {
+        self.vblank = true;
+    }
+
     pub fn delay_timer(&mut self) {
         if self.delay > 0 {
             self.delay -= 1;
@@ -373,20 +863,34 @@ impl CPU {
         }
     }
 
+    pub fn sound_active(&self) -> bool {
+        self.sound > 0
+    }
+
+    pub fn audio_pattern(&self) -> Option<&[u8; 16]> {
+        self.pattern.as_ref()
+    }
+
+    pub fn pitch(&self) -> u8 {
+        self.pitch
+    }
+
     pub fn log_value<T>(&self, label: &str, value: T)
     where
         T: Into<u16>,
     {
-        let val: u16 = value.try_into().unwrap();
+        let val: u16 = value.into();
         println!(
             "{0} ADDR {1:#06x} : VAL {2:#06x} {2:#018b} {2:#05}",
             label, self.latest_addr, val
         );
     }
 
-    pub fn log_addr(&mut self, mem: &mut Memory, addr: u16) {
+    pub fn log_addr(&mut self, mem: &Memory, addr: u16) -> Result<(), Chip8Error> {
+        let value = self.read(mem, addr as usize)?;
         print!("ADDR : {:#06x}", addr);
-        self.log_value("|", mem[addr]);
+        self.log_value("|", value);
+        Ok(())
     }
 
     pub fn log_last(&mut self) {
@@ -397,7 +901,520 @@ impl CPU {
         self.log_value("|", self.latest_fetch);
     }
 
-    pub fn pc_valid(&self) -> bool {
-        (self.pc as usize) < Memory::get_cap()
+    pub fn pc_valid(&self, mem: &Memory) -> bool {
+        (self.pc as usize) < mem.get_cap()
+    }
+}
+
+impl Default for CPU {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(cpu: &mut CPU, op: OpCode) {
+        let mut memory = Memory::new();
+        let mut display = Display::new();
+        let mut keyboard = Keyboard::new();
+        cpu.execute(&mut memory, &mut display, &mut keyboard, &op)
+            .unwrap();
+    }
+
+    fn cpu_with(quirks: Quirks, registers: &[(usize, u8)]) -> CPU {
+        let mut cpu = CPU::with_quirks(quirks);
+        for (reg, value) in registers {
+            cpu.set_register(*reg, *value);
+        }
+        cpu
+    }
+
+    fn draw(display: &mut Display, clip: bool, sprite: &[u8], x: u8, y: u8) -> u8 {
+        let quirks = Quirks {
+            clip_sprites: clip,
+            display_wait: false,
+            ..Quirks::cosmac_vip()
+        };
+        let mut cpu = cpu_with(quirks, &[(0x0, x), (0x1, y), (0xF, 0xAA)]);
+        let mut memory = Memory::new();
+        for (offset, byte) in sprite.iter().enumerate() {
+            *memory.get_mut(0x300 + offset).unwrap() = *byte;
+        }
+        cpu.set_i(0x300);
+        let op = OpCode::DRAW(0x0, 0x1, sprite.len() as u8);
+        cpu.execute(&mut memory, display, &mut Keyboard::new(), &op)
+            .unwrap();
+        cpu.get_registers()[0xF]
+    }
+
+    fn lit(display: &Display) -> Vec<(u32, u32)> {
+        let (width, height) = display.size();
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|(x, y)| display.get_pixel(*x, *y))
+            .collect()
+    }
+
+    #[test]
+    fn draw_reads_the_most_significant_bit_first() {
+        let mut display = Display::new();
+        draw(&mut display, true, &[0b1000_0001], 10, 5);
+        assert_eq!(lit(&display), [(10, 5), (17, 5)]);
+    }
+
+    #[test]
+    fn draw_at_the_left_edge() {
+        let mut display = Display::new();
+        draw(&mut display, true, &[0b1100_0000], 0, 0);
+        assert_eq!(lit(&display), [(0, 0), (1, 0)]);
+    }
+
+    #[test]
+    fn draw_wraps_the_start_position() {
+        let mut display = Display::new();
+        draw(&mut display, true, &[0b1000_0000], 70, 33);
+        assert_eq!(lit(&display), [(6, 1)]);
+    }
+
+    #[test]
+    fn draw_clips_at_the_edges() {
+        let mut display = Display::new();
+        draw(&mut display, true, &[0xFF, 0xFF], 60, 31);
+        assert_eq!(lit(&display), [(60, 31), (61, 31), (62, 31), (63, 31)]);
+    }
+
+    #[test]
+    fn draw_wraps_at_the_edges_without_clipping() {
+        let mut display = Display::new();
+        draw(&mut display, false, &[0b1000_0001, 0b1000_0000], 60, 31);
+        assert_eq!(lit(&display), [(60, 0), (3, 31), (60, 31)]);
+    }
+
+    #[test]
+    fn draw_sets_vf_only_when_a_lit_pixel_is_erased() {
+        let mut display = Display::new();
+        assert_eq!(draw(&mut display, true, &[0b1010_0000], 0, 0), 0);
+        // overlapping only unlit pixels is not a collision
+        assert_eq!(draw(&mut display, true, &[0b0101_0000], 0, 0), 0);
+        assert_eq!(lit(&display), [(0, 0), (1, 0), (2, 0), (3, 0)]);
+
+        assert_eq!(draw(&mut display, true, &[0b1000_0000], 0, 0), 1);
+        assert_eq!(lit(&display), [(1, 0), (2, 0), (3, 0)]);
+    }
+
+    #[test]
+    fn draw_clears_vf_when_nothing_is_erased() {
+        let mut display = Display::new();
+        // draw starts VF at 0xAA, it must not survive
+        assert_eq!(draw(&mut display, true, &[0x00], 0, 0), 0);
+        assert_eq!(draw(&mut display, true, &[0x80], 5, 5), 0);
+    }
+
+    #[test]
+    fn add_const_wraps_and_leaves_vf() {
+        let mut cpu = cpu_with(Quirks::default(), &[(0x1, 0xFF), (0xF, 0x7)]);
+        run(&mut cpu, OpCode::ADD_CONST(0x1, 0x02));
+        assert_eq!(cpu.get_registers()[0x1], 0x01);
+        assert_eq!(cpu.get_registers()[0xF], 0x7);
+
+        // VF is just another register to 7XNN
+        run(&mut cpu, OpCode::ADD_CONST(0xF, 0xFF));
+        assert_eq!(cpu.get_registers()[0xF], 0x06);
+    }
+
+    #[test]
+    fn add_reg_sets_carry() {
+        let mut cpu = cpu_with(Quirks::default(), &[(0x1, 0xF0), (0x2, 0x0F)]);
+        run(&mut cpu, OpCode::ADD_REG(0x1, 0x2));
+        assert_eq!(
+            (cpu.get_registers()[0x1], cpu.get_registers()[0xF]),
+            (0xFF, 0)
+        );
+
+        run(&mut cpu, OpCode::ADD_REG(0x1, 0x2));
+        assert_eq!(
+            (cpu.get_registers()[0x1], cpu.get_registers()[0xF]),
+            (0x0E, 1)
+        );
+
+        // with VF as X the carry overwrites the sum
+        let mut cpu = cpu_with(Quirks::default(), &[(0xF, 0xFF), (0x2, 0x02)]);
+        run(&mut cpu, OpCode::ADD_REG(0xF, 0x2));
+        assert_eq!(cpu.get_registers()[0xF], 1);
+    }
+
+    #[test]
+    fn sub_reg_clears_vf_on_borrow() {
+        let mut cpu = cpu_with(Quirks::default(), &[(0x1, 0x05), (0x2, 0x03)]);
+        run(&mut cpu, OpCode::SUB_REG(0x1, 0x2));
+        assert_eq!(
+            (cpu.get_registers()[0x1], cpu.get_registers()[0xF]),
+            (0x02, 1)
+        );
+
+        run(&mut cpu, OpCode::SUB_REG(0x1, 0x2));
+        assert_eq!(
+            (cpu.get_registers()[0x1], cpu.get_registers()[0xF]),
+            (0xFF, 0)
+        );
+
+        // equal values do not borrow
+        let mut cpu = cpu_with(Quirks::default(), &[(0x1, 0x03), (0x2, 0x03)]);
+        run(&mut cpu, OpCode::SUB_REG(0x1, 0x2));
+        assert_eq!(
+            (cpu.get_registers()[0x1], cpu.get_registers()[0xF]),
+            (0x00, 1)
+        );
+
+        let mut cpu = cpu_with(Quirks::default(), &[(0xF, 0x01), (0x2, 0x03)]);
+        run(&mut cpu, OpCode::SUB_REG(0xF, 0x2));
+        assert_eq!(cpu.get_registers()[0xF], 0);
+    }
+
+    #[test]
+    fn subtract_is_vy_minus_vx() {
+        let mut cpu = cpu_with(Quirks::default(), &[(0x1, 0x03), (0x2, 0x05)]);
+        run(&mut cpu, OpCode::SUBTRACT(0x1, 0x2));
+        assert_eq!(
+            (cpu.get_registers()[0x1], cpu.get_registers()[0xF]),
+            (0x02, 1)
+        );
+
+        let mut cpu = cpu_with(Quirks::default(), &[(0x1, 0x05), (0x2, 0x03)]);
+        run(&mut cpu, OpCode::SUBTRACT(0x1, 0x2));
+        assert_eq!(
+            (cpu.get_registers()[0x1], cpu.get_registers()[0xF]),
+            (0xFE, 0)
+        );
+
+        let mut cpu = cpu_with(Quirks::default(), &[(0xF, 0x01), (0x2, 0x05)]);
+        run(&mut cpu, OpCode::SUBTRACT(0xF, 0x2));
+        assert_eq!(cpu.get_registers()[0xF], 1);
+    }
+
+    #[test]
+    fn shift_right_moves_the_low_bit_to_vf() {
+        // the VIP shifts VY into VX
+        let mut cpu = cpu_with(Quirks::cosmac_vip(), &[(0x1, 0x00), (0x2, 0x05)]);
+        run(&mut cpu, OpCode::BSHIFT_RGHT(0x1, 0x2));
+        assert_eq!(
+            (cpu.get_registers()[0x1], cpu.get_registers()[0xF]),
+            (0x02, 1)
+        );
+
+        // SUPER-CHIP shifts VX in place
+        let mut cpu = cpu_with(Quirks::superchip(), &[(0x1, 0x04), (0x2, 0x05)]);
+        run(&mut cpu, OpCode::BSHIFT_RGHT(0x1, 0x2));
+        assert_eq!(
+            (cpu.get_registers()[0x1], cpu.get_registers()[0xF]),
+            (0x02, 0)
+        );
+
+        let mut cpu = cpu_with(Quirks::superchip(), &[(0xF, 0x04)]);
+        run(&mut cpu, OpCode::BSHIFT_RGHT(0xF, 0x2));
+        assert_eq!(cpu.get_registers()[0xF], 0);
+    }
+
+    #[test]
+    fn shift_left_moves_the_high_bit_to_vf() {
+        let mut cpu = cpu_with(Quirks::cosmac_vip(), &[(0x1, 0x00), (0x2, 0x81)]);
+        run(&mut cpu, OpCode::BSHIFT_LEFT(0x1, 0x2));
+        assert_eq!(
+            (cpu.get_registers()[0x1], cpu.get_registers()[0xF]),
+            (0x02, 1)
+        );
+
+        let mut cpu = cpu_with(Quirks::superchip(), &[(0x1, 0x41), (0x2, 0x81)]);
+        run(&mut cpu, OpCode::BSHIFT_LEFT(0x1, 0x2));
+        assert_eq!(
+            (cpu.get_registers()[0x1], cpu.get_registers()[0xF]),
+            (0x82, 0)
+        );
+
+        let mut cpu = cpu_with(Quirks::superchip(), &[(0xF, 0x01)]);
+        run(&mut cpu, OpCode::BSHIFT_LEFT(0xF, 0x2));
+        assert_eq!(cpu.get_registers()[0xF], 0);
+    }
+
+    #[test]
+    fn pc_wraps_at_the_end_of_xo_memory() {
+        let mut memory = Memory::with_capacity(Memory::get_xo_cap());
+        let mut display = Display::new();
+        let mut keyboard = Keyboard::new();
+
+        let mut cpu = CPU::with_quirks(Quirks::xo_chip());
+        cpu.set_pc(0xFFFE);
+        cpu.execute(&mut memory, &mut display, &mut keyboard, &OpCode::CLEAR)
+            .unwrap();
+        assert_eq!(cpu.get_pc(), 0x0000);
+
+        // a skip over F000 NNNN at the end jumps four bytes
+        *memory.get_mut(0x0000).unwrap() = 0xF0;
+        *memory.get_mut(0x0001).unwrap() = 0x00;
+        cpu.set_pc(0xFFFE);
+        cpu.execute(
+            &mut memory,
+            &mut display,
+            &mut keyboard,
+            &OpCode::EQ(0x0, 0x00),
+        )
+        .unwrap();
+        assert_eq!(cpu.get_pc(), 0x0004);
+
+        cpu.set_pc(0xFFFC);
+        cpu.execute(&mut memory, &mut display, &mut keyboard, &OpCode::LONG_I)
+            .unwrap();
+        assert_eq!(cpu.get_pc(), 0x0000);
+    }
+
+    fn execute(cpu: &mut CPU, op: OpCode) -> Result<(), Chip8Error> {
+        cpu.execute(
+            &mut Memory::new(),
+            &mut Display::new(),
+            &mut Keyboard::new(),
+            &op,
+        )
+    }
+
+    #[test]
+    fn call_overflows_past_the_stack_depth() {
+        for (quirks, depth) in [(Quirks::cosmac_vip(), 12), (Quirks::superchip(), 16)] {
+            let mut cpu = CPU::with_quirks(quirks);
+            for _ in 0..depth {
+                execute(&mut cpu, OpCode::CALL(0x300)).unwrap();
+            }
+            assert_eq!(cpu.get_sp(), depth);
+            assert!(matches!(
+                execute(&mut cpu, OpCode::CALL(0x300)),
+                Err(Chip8Error::StackOverflow { .. })
+            ));
+            assert_eq!(cpu.get_sp(), depth);
+        }
+    }
+
+    #[test]
+    fn return_underflows_an_empty_stack() {
+        let mut cpu = CPU::new();
+        assert!(matches!(
+            execute(&mut cpu, OpCode::RETURN),
+            Err(Chip8Error::StackUnderflow { .. })
+        ));
+
+        // and otherwise resumes after the CALL
+        cpu.set_pc(0x204);
+        execute(&mut cpu, OpCode::CALL(0x300)).unwrap();
+        execute(&mut cpu, OpCode::RETURN).unwrap();
+        assert_eq!(cpu.get_pc(), 0x206);
+    }
+
+    #[test]
+    fn stack_depth_follows_the_quirks() {
+        let quirks = Quirks {
+            stack_depth: 2,
+            ..Quirks::superchip()
+        };
+        let mut cpu = CPU::with_quirks(quirks);
+        execute(&mut cpu, OpCode::CALL(0x300)).unwrap();
+        execute(&mut cpu, OpCode::CALL(0x300)).unwrap();
+        assert!(execute(&mut cpu, OpCode::CALL(0x300)).is_err());
+    }
+
+    fn execute_in(cpu: &mut CPU, memory: &mut Memory, op: OpCode) -> Result<(), Chip8Error> {
+        cpu.execute(memory, &mut Display::new(), &mut Keyboard::new(), &op)
+    }
+
+    #[test]
+    fn store_and_load_include_vx() {
+        let mut memory = Memory::new();
+        let mut cpu = cpu_with(
+            Quirks::superchip(),
+            &[(0x0, 1), (0x1, 2), (0x2, 3), (0x3, 4)],
+        );
+        cpu.set_i(0x300);
+        execute_in(&mut cpu, &mut memory, OpCode::DUMP(0x2)).unwrap();
+        let stored: Vec<_> = (0x300..0x304)
+            .map(|addr| memory.get(addr).unwrap())
+            .collect();
+        assert_eq!(stored, [1, 2, 3, 0]);
+
+        let mut cpu = CPU::with_quirks(Quirks::superchip());
+        cpu.set_i(0x300);
+        execute_in(&mut cpu, &mut memory, OpCode::LOAD(0x2)).unwrap();
+        assert_eq!(cpu.get_registers()[..4], [1, 2, 3, 0]);
+    }
+
+    #[test]
+    fn store_and_load_move_i_per_preset() {
+        for (quirks, moved) in [
+            (Quirks::cosmac_vip(), true),
+            (Quirks::superchip(), false),
+            (Quirks::xo_chip(), true),
+        ] {
+            for op in [OpCode::DUMP(0x4), OpCode::LOAD(0x4)] {
+                let mut cpu = CPU::with_quirks(quirks);
+                cpu.set_i(0x300);
+                execute_in(&mut cpu, &mut Memory::new(), op).unwrap();
+                assert_eq!(cpu.get_i(), if moved { 0x305 } else { 0x300 });
+            }
+        }
+    }
+
+    #[test]
+    fn store_and_load_past_the_end_fault_untouched() {
+        let mut memory = Memory::new();
+        let mut cpu = cpu_with(Quirks::cosmac_vip(), &[(0x0, 0xAA), (0x1, 0xBB)]);
+        cpu.set_i(0xFFF);
+        assert!(matches!(
+            execute_in(&mut cpu, &mut memory, OpCode::DUMP(0x1)),
+            Err(Chip8Error::OutOfBounds {
+                address: 0x1000,
+                ..
+            })
+        ));
+        // nothing was written, not even the byte that fits
+        assert_eq!(memory.get(0xFFF), Some(0));
+        assert_eq!(cpu.get_i(), 0xFFF);
+
+        *memory.get_mut(0xFFF).unwrap() = 0x77;
+        assert!(matches!(
+            execute_in(&mut cpu, &mut memory, OpCode::LOAD(0x1)),
+            Err(Chip8Error::OutOfBounds { .. })
+        ));
+        assert_eq!(cpu.get_registers()[..2], [0xAA, 0xBB]);
+        assert_eq!(cpu.get_i(), 0xFFF);
+    }
+
+    #[test]
+    fn add_to_i_sets_vf_only_past_0xfff_when_asked() {
+        let amiga = Quirks {
+            addi_sets_vf: true,
+            ..Quirks::cosmac_vip()
+        };
+        let mut cpu = cpu_with(amiga, &[(0x0, 0x01), (0xF, 0xAA)]);
+        cpu.set_i(0xFFE);
+        run(&mut cpu, OpCode::ADDI(0x0));
+        assert_eq!((cpu.get_i(), cpu.get_registers()[0xF]), (0xFFF, 0));
+        run(&mut cpu, OpCode::ADDI(0x0));
+        assert_eq!((cpu.get_i(), cpu.get_registers()[0xF]), (0x1000, 1));
+
+        let mut cpu = cpu_with(Quirks::cosmac_vip(), &[(0x0, 0x01), (0xF, 0xAA)]);
+        cpu.set_i(0xFFF);
+        run(&mut cpu, OpCode::ADDI(0x0));
+        assert_eq!((cpu.get_i(), cpu.get_registers()[0xF]), (0x1000, 0xAA));
+    }
+
+    #[test]
+    fn draw_16x16_sprites_in_hires() {
+        let mut display = Display::new();
+        display.set_hires(true);
+        let mut cpu = cpu_with(Quirks::superchip(), &[(0x0, 100), (0x1, 40)]);
+        let mut memory = Memory::new();
+        // the first and last rows are a pixel at each end
+        for row in [0, 15] {
+            *memory.get_mut(0x300 + row * 2).unwrap() = 0x80;
+            *memory.get_mut(0x301 + row * 2).unwrap() = 0x01;
+        }
+        cpu.set_i(0x300);
+        let op = OpCode::DRAW(0x0, 0x1, 0);
+        cpu.execute(&mut memory, &mut display, &mut Keyboard::new(), &op)
+            .unwrap();
+        assert_eq!(lit(&display), [(100, 40), (115, 40), (100, 55), (115, 55)]);
+    }
+
+    #[test]
+    fn resolution_and_exit_instructions() {
+        let mut display = Display::new();
+        let mut cpu = CPU::with_quirks(Quirks::superchip());
+        let mut memory = Memory::new();
+        let mut keyboard = Keyboard::new();
+        cpu.execute(&mut memory, &mut display, &mut keyboard, &OpCode::HIRES)
+            .unwrap();
+        assert_eq!(display.size(), (128, 64));
+        cpu.execute(&mut memory, &mut display, &mut keyboard, &OpCode::LORES)
+            .unwrap();
+        assert_eq!(display.size(), (64, 32));
+
+        cpu.set_pc(0x240);
+        assert!(matches!(
+            cpu.execute(&mut memory, &mut display, &mut keyboard, &OpCode::EXIT),
+            Err(Chip8Error::Exit { .. })
+        ));
+    }
+
+    #[test]
+    fn big_font_points_past_the_small_one() {
+        let mut cpu = cpu_with(Quirks::superchip(), &[(0x3, 0x2)]);
+        run(&mut cpu, OpCode::BIG_SPRI(0x3));
+        assert_eq!(cpu.get_i(), 80 + 2 * 10);
+
+        run(&mut cpu, OpCode::SPRI(0x3));
+        assert_eq!(cpu.get_i(), 2 * 5);
+    }
+
+    #[test]
+    fn rpl_flags_keep_registers() {
+        let mut cpu = cpu_with(
+            Quirks::superchip(),
+            &[(0x0, 1), (0x1, 2), (0x2, 3), (0x9, 9)],
+        );
+        // only eight flags exist, asking for more stops at V7
+        run(&mut cpu, OpCode::SAVE_FLAGS(0xF));
+        for reg in 0..16 {
+            cpu.set_register(reg, 0);
+        }
+
+        run(&mut cpu, OpCode::LOAD_FLAGS(0x1));
+        assert_eq!(cpu.get_registers()[..3], [1, 2, 0]);
+        run(&mut cpu, OpCode::LOAD_FLAGS(0xF));
+        assert_eq!(cpu.get_registers()[..3], [1, 2, 3]);
+        assert_eq!(cpu.get_registers()[0x9], 0);
+    }
+
+    #[test]
+    fn schip_opcodes_decode() {
+        assert_eq!(OpCode::from(0x00C5), OpCode::SCROLL_DOWN(5));
+        assert_eq!(OpCode::from(0x00FB), OpCode::SCROLL_RIGHT);
+        assert_eq!(OpCode::from(0x00FC), OpCode::SCROLL_LEFT);
+        assert_eq!(OpCode::from(0x00FD), OpCode::EXIT);
+        assert_eq!(OpCode::from(0x00FE), OpCode::LORES);
+        assert_eq!(OpCode::from(0x00FF), OpCode::HIRES);
+        assert_eq!(OpCode::from(0xD120), OpCode::DRAW(0x1, 0x2, 0));
+        assert_eq!(OpCode::from(0xF430), OpCode::BIG_SPRI(0x4));
+        assert_eq!(OpCode::from(0xF775), OpCode::SAVE_FLAGS(0x7));
+        assert_eq!(OpCode::from(0xF785), OpCode::LOAD_FLAGS(0x7));
+    }
+
+    #[test]
+    fn skip_if_key_follows_held_keys() {
+        let mut keyboard = Keyboard::new();
+        keyboard.press(0x1);
+        keyboard.press(0xF);
+        let mut memory = Memory::new();
+        let mut display = Display::new();
+
+        for (key, pressed) in [(0x1, true), (0xF, true), (0x2, false)] {
+            let mut cpu = cpu_with(Quirks::cosmac_vip(), &[(0x4, key)]);
+            cpu.execute(
+                &mut memory,
+                &mut display,
+                &mut keyboard,
+                &OpCode::KEY_P(0x4),
+            )
+            .unwrap();
+            assert_eq!(cpu.get_pc(), if pressed { 0x204 } else { 0x202 });
+
+            let mut cpu = cpu_with(Quirks::cosmac_vip(), &[(0x4, key)]);
+            cpu.execute(
+                &mut memory,
+                &mut display,
+                &mut keyboard,
+                &OpCode::KEY_NP(0x4),
+            )
+            .unwrap();
+            assert_eq!(cpu.get_pc(), if pressed { 0x202 } else { 0x204 });
+        }
     }
 }
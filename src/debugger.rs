@@ -0,0 +1,647 @@
+use std::{
+    fmt,
+    io::{BufRead, Write},
+};
+
+use crate::{
+    chip8::CHIP8,
+    cpu::OpCode,
+    error::Chip8Error,
+    stepper::{Halt, Stepper},
+};
+
+const HELP: &str = "\
+break <addr> [if <cond>]   stop when PC reaches addr, e.g. `break 0x2a0 if V3 == 0x10`
+watch <target>             stop when a register (V0-VF, I, DT, ST) or memory (0x300, 0x300..0x310) changes
+delete <id>                remove a breakpoint or watchpoint
+info                       list breakpoints and watchpoints
+continue                   run until something stops the program
+step [n]                   run n instructions (default 1)
+next                       run one instruction, stepping over subroutine calls
+finish                     run until the current subroutine returns
+regs                       show the registers
+mem <addr> [len]           dump memory
+stack                      show the calls on the stack and where they return
+disas [addr] [n]           disassemble n instructions (default 10) from addr (default PC)
+quit                       leave the debugger
+Conditions compare registers, memory ([0x300]) and numbers with == != < <= > >=.
+An empty line repeats the last command.";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Register {
+    V(u8),
+    I,
+    PC,
+    SP,
+    DT,
+    ST,
+}
+
+impl Register {
+    fn parse(text: &str) -> Option<Self> {
+        let text = text.to_ascii_uppercase();
+        match text.as_str() {
+            "I" => Some(Register::I),
+            "PC" => Some(Register::PC),
+            "SP" => Some(Register::SP),
+            "DT" => Some(Register::DT),
+            "ST" => Some(Register::ST),
+            _ => {
+                let idx = text.strip_prefix('V')?;
+                let idx = u8::from_str_radix(idx, 16)
+                    .ok()
+                    .filter(|_| idx.len() == 1)?;
+                Some(Register::V(idx))
+            }
+        }
+    }
+
+    fn read(&self, chip8: &CHIP8) -> u16 {
+        let cpu = chip8.get_cpu();
+        match self {
+            Register::V(idx) => cpu.get_registers()[*idx as usize] as u16,
+            Register::I => cpu.get_i(),
+            Register::PC => cpu.get_pc(),
+            Register::SP => cpu.get_sp() as u16,
+            Register::DT => cpu.get_delay() as u16,
+            Register::ST => cpu.get_sound() as u16,
+        }
+    }
+}
+
+impl fmt::Display for Register {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Register::V(idx) => write!(f, "V{:X}", idx),
+            other => write!(f, "{:?}", other),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Operand {
+    Register(Register),
+    Memory(u16),
+    Value(u16),
+}
+
+impl Operand {
+    fn parse(text: &str) -> Option<Self> {
+        if let Some(addr) = text.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+            return parse_number(addr).map(Operand::Memory);
+        }
+        Register::parse(text)
+            .map(Operand::Register)
+            .or_else(|| parse_number(text).map(Operand::Value))
+    }
+
+    fn read(&self, chip8: &CHIP8) -> u16 {
+        match self {
+            Operand::Register(reg) => reg.read(chip8),
+            Operand::Memory(addr) => chip8.get_memory().get(*addr as usize).unwrap_or(0) as u16,
+            Operand::Value(value) => *value,
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Register(reg) => write!(f, "{}", reg),
+            Operand::Memory(addr) => write!(f, "[{:#06x}]", addr),
+            Operand::Value(value) => write!(f, "{:#x}", value),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Condition {
+    left: Operand,
+    op: &'static str,
+    right: Operand,
+}
+
+impl Condition {
+    fn parse(text: &str) -> Result<Self, String> {
+        // two character operators first, so `<=` is not read as `<`
+        for op in ["==", "!=", "<=", ">=", "<", ">"] {
+            if let Some((left, right)) = text.split_once(op) {
+                let operand = |text: &str| {
+                    Operand::parse(text.trim())
+                        .ok_or_else(|| format!("`{}` Is Not A Register, Memory Or Number", text))
+                };
+                return Ok(Self {
+                    left: operand(left)?,
+                    op,
+                    right: operand(right)?,
+                });
+            }
+        }
+        Err(format!("`{}` Is Not A Condition", text))
+    }
+
+    fn holds(&self, chip8: &CHIP8) -> bool {
+        let (left, right) = (self.left.read(chip8), self.right.read(chip8));
+        match self.op {
+            "==" => left == right,
+            "!=" => left != right,
+            "<=" => left <= right,
+            ">=" => left >= right,
+            "<" => left < right,
+            _ => left > right,
+        }
+    }
+}
+
+impl fmt::Display for Condition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {} {}", self.left, self.op, self.right)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WatchTarget {
+    Register(Register),
+    // first and last address, so a watch can reach 0xFFFF
+    Memory(u16, u16),
+}
+
+impl WatchTarget {
+    fn parse(text: &str) -> Option<Self> {
+        if let Some(reg) = Register::parse(text) {
+            return Some(WatchTarget::Register(reg));
+        }
+        match text.split_once("..") {
+            Some((start, end)) => {
+                let (start, end) = (parse_number(start)?, parse_number(end)?);
+                (start < end).then_some(WatchTarget::Memory(start, end - 1))
+            }
+            None => parse_number(text).map(|addr| WatchTarget::Memory(addr, addr)),
+        }
+    }
+
+    fn snapshot(&self, chip8: &CHIP8) -> Vec<u16> {
+        match self {
+            WatchTarget::Register(reg) => vec![reg.read(chip8)],
+            WatchTarget::Memory(start, last) => (*start..=*last)
+                .map(|addr| chip8.get_memory().get(addr as usize).unwrap_or(0) as u16)
+                .collect(),
+        }
+    }
+}
+
+impl fmt::Display for WatchTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            WatchTarget::Register(reg) => write!(f, "{}", reg),
+            WatchTarget::Memory(start, last) if start == last => write!(f, "{:#06x}", start),
+            WatchTarget::Memory(start, last) => {
+                write!(f, "{:#06x}..{:#06x}", start, *last as u32 + 1)
+            }
+        }
+    }
+}
+
+struct Breakpoint {
+    id: usize,
+    address: u16,
+    condition: Option<Condition>,
+}
+
+struct Watchpoint {
+    id: usize,
+    target: WatchTarget,
+    last: Vec<u16>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Command {
+    Break(u16, Option<Condition>),
+    Watch(WatchTarget),
+    Delete(usize),
+    Info,
+    Continue,
+    Step(u32),
+    Next,
+    Finish,
+    Regs,
+    Mem(u16, u16),
+    Stack,
+    Disas(Option<u16>, u16),
+    Help,
+    Quit,
+}
+
+impl Command {
+    pub fn parse(line: &str) -> Result<Self, String> {
+        let line = line.trim();
+        let (name, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        let args: Vec<&str> = rest.split_whitespace().collect();
+        let number = |idx: usize| -> Result<Option<u16>, String> {
+            args.get(idx)
+                .map(|arg| parse_number(arg).ok_or_else(|| format!("`{}` Is Not A Number", arg)))
+                .transpose()
+        };
+
+        match name {
+            "break" | "b" => {
+                let address = number(0)?.ok_or("break Needs An Address")?;
+                let condition = match rest.split_once(" if ") {
+                    Some((_, condition)) => Some(Condition::parse(condition.trim())?),
+                    None if args.len() > 1 => return Err("Expected `if` After The Address".into()),
+                    None => None,
+                };
+                Ok(Command::Break(address, condition))
+            }
+            "watch" | "w" => {
+                let target = args.first().ok_or("watch Needs A Register Or Address")?;
+                WatchTarget::parse(target)
+                    .map(Command::Watch)
+                    .ok_or_else(|| format!("Cannot Watch `{}`", target))
+            }
+            "delete" | "d" => Ok(Command::Delete(
+                number(0)?.ok_or("delete Needs An Id")? as usize
+            )),
+            "info" | "i" => Ok(Command::Info),
+            "continue" | "c" => Ok(Command::Continue),
+            "step" | "s" => Ok(Command::Step(number(0)?.unwrap_or(1) as u32)),
+            "next" | "n" => Ok(Command::Next),
+            "finish" | "f" => Ok(Command::Finish),
+            "regs" | "r" => Ok(Command::Regs),
+            "mem" | "x" => Ok(Command::Mem(
+                number(0)?.ok_or("mem Needs An Address")?,
+                number(1)?.unwrap_or(64),
+            )),
+            "stack" | "bt" => Ok(Command::Stack),
+            "disas" | "l" => Ok(Command::Disas(number(0)?, number(1)?.unwrap_or(10))),
+            "help" | "h" | "?" => Ok(Command::Help),
+            "quit" | "q" => Ok(Command::Quit),
+            other => Err(format!("Unknown Command `{}`, Try `help`", other)),
+        }
+    }
+}
+
+// why a run stopped
+enum Stop {
+    Done,
+    Breakpoint(usize, u16),
+    Watchpoint(usize, Vec<u16>, Vec<u16>),
+    Fault(Chip8Error),
+}
+
+// A terminal debugger wrapped around a CHIP8, driving it one CHIP8::step at a time
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    watchpoints: Vec<Watchpoint>,
+    next_id: usize,
+    stepper: Stepper,
+}
+
+impl Debugger {
+    pub fn new() -> Self {
+        Self {
+            breakpoints: Vec::new(),
+            watchpoints: Vec::new(),
+            next_id: 1,
+            stepper: Stepper::new(),
+        }
+    }
+
+    pub fn set_realtime(&mut self, realtime: bool) {
+        self.stepper.set_realtime(realtime);
+    }
+
+    pub fn repl<R: BufRead, W: Write>(
+        &mut self,
+        chip8: &mut CHIP8,
+        input: R,
+        mut output: W,
+    ) -> Result<(), Chip8Error> {
+        let mut lines = input.lines();
+        let mut last: Option<Command> = None;
+
+        loop {
+            write!(output, "(chip8) ")?;
+            output.flush()?;
+
+            let line = match lines.next() {
+                Some(line) => line?,
+                None => return Ok(()),
+            };
+
+            let command = if line.trim().is_empty() {
+                match &last {
+                    Some(command) => Ok(command.clone()),
+                    None => continue,
+                }
+            } else {
+                Command::parse(&line)
+            };
+
+            match command {
+                Ok(Command::Quit) => return Ok(()),
+                Ok(command) => {
+                    self.command(chip8, &command, &mut output)?;
+                    last = Some(command);
+                }
+                Err(message) => writeln!(output, "{}", message)?,
+            }
+        }
+    }
+
+    pub fn command<W: Write>(
+        &mut self,
+        chip8: &mut CHIP8,
+        command: &Command,
+        output: &mut W,
+    ) -> Result<(), Chip8Error> {
+        match command {
+            Command::Break(address, condition) => {
+                let id = self.take_id();
+                self.breakpoints.push(Breakpoint {
+                    id,
+                    address: *address,
+                    condition: *condition,
+                });
+                writeln!(output, "Breakpoint {} At {:#06x}", id, address)?;
+            }
+            Command::Watch(target) => {
+                let id = self.take_id();
+                self.watchpoints.push(Watchpoint {
+                    id,
+                    target: *target,
+                    last: target.snapshot(chip8),
+                });
+                writeln!(output, "Watchpoint {} On {}", id, target)?;
+            }
+            Command::Delete(id) => {
+                let count = self.breakpoints.len() + self.watchpoints.len();
+                self.breakpoints.retain(|b| b.id != *id);
+                self.watchpoints.retain(|w| w.id != *id);
+                if count == self.breakpoints.len() + self.watchpoints.len() {
+                    writeln!(output, "No Breakpoint Or Watchpoint {}", id)?;
+                }
+            }
+            Command::Info => {
+                for b in &self.breakpoints {
+                    match &b.condition {
+                        Some(c) => {
+                            writeln!(output, "{:>3}  break {:#06x} if {}", b.id, b.address, c)?
+                        }
+                        None => writeln!(output, "{:>3}  break {:#06x}", b.id, b.address)?,
+                    }
+                }
+                for w in &self.watchpoints {
+                    writeln!(output, "{:>3}  watch {}", w.id, w.target)?;
+                }
+            }
+            Command::Continue => {
+                let stop = self.run(chip8, |_| false, u64::MAX);
+                self.report(chip8, stop, output)?;
+            }
+            Command::Step(count) => {
+                let stop = self.run(chip8, |_| false, *count as u64);
+                self.report(chip8, stop, output)?;
+            }
+            Command::Next => {
+                let cpu = chip8.get_cpu();
+                let stop = if is_call(chip8) {
+                    let (ret, sp) = (cpu.get_pc().wrapping_add(2), cpu.get_sp());
+                    self.run(
+                        chip8,
+                        move |c| c.get_cpu().get_pc() == ret && c.get_cpu().get_sp() == sp,
+                        u64::MAX,
+                    )
+                } else {
+                    self.run(chip8, |_| false, 1)
+                };
+                self.report(chip8, stop, output)?;
+            }
+            Command::Finish => {
+                let sp = chip8.get_cpu().get_sp();
+                if sp == 0 {
+                    writeln!(output, "Not Inside A Subroutine")?;
+                } else {
+                    let stop = self.run(chip8, move |c| c.get_cpu().get_sp() < sp, u64::MAX);
+                    self.report(chip8, stop, output)?;
+                }
+            }
+            Command::Regs => write_regs(chip8, output)?,
+            Command::Mem(address, len) => {
+                let memory = chip8.get_memory();
+                let start = *address as usize;
+                for line in (start..start + *len as usize).step_by(16) {
+                    write!(output, "{:#06x}:", line)?;
+                    for addr in line..(line + 16).min(start + *len as usize) {
+                        match memory.get(addr) {
+                            Some(byte) => write!(output, " {:02x}", byte)?,
+                            None => write!(output, " --")?,
+                        }
+                    }
+                    writeln!(output)?;
+                }
+            }
+            Command::Stack => {
+                let stack = chip8.get_cpu().get_stack();
+                writeln!(output, "Stack Depth {} Of {}", stack.len(), stack.depth())?;
+                for (depth, addr) in stack.addresses().iter().enumerate().rev() {
+                    writeln!(
+                        output,
+                        "#{:<2} {:#06x}  returns to {:#06x}",
+                        depth,
+                        addr,
+                        addr.wrapping_add(2)
+                    )?;
+                }
+            }
+            Command::Disas(address, count) => {
+                let address = address.unwrap_or(chip8.get_cpu().get_pc());
+                write_disas(chip8, address, *count, output)?;
+            }
+            Command::Help => writeln!(output, "{}", HELP)?,
+            Command::Quit => {}
+        }
+        Ok(())
+    }
+
+    fn take_id(&mut self) -> usize {
+        self.next_id += 1;
+        self.next_id - 1
+    }
+
+    // runs up to `limit` instructions, stopping early at breakpoints,
+    // watchpoints, faults or once `until` holds after an instruction
+    fn run<F: Fn(&CHIP8) -> bool>(&mut self, chip8: &mut CHIP8, until: F, limit: u64) -> Stop {
+        if limit == 0 {
+            return Stop::Done;
+        }
+        let breakpoints = &self.breakpoints;
+        let watchpoints = &mut self.watchpoints;
+        let mut executed = 0;
+
+        let halt = self.stepper.run(
+            chip8,
+            |chip8| {
+                let pc = chip8.get_cpu().get_pc();
+                breakpoints
+                    .iter()
+                    .find(|b| b.address == pc && b.condition.is_none_or(|c| c.holds(chip8)))
+                    .map(|b| Stop::Breakpoint(b.id, pc))
+            },
+            |chip8, _| {
+                for w in watchpoints.iter_mut() {
+                    let now = w.target.snapshot(chip8);
+                    if now != w.last {
+                        let old = std::mem::replace(&mut w.last, now.clone());
+                        return Some(Stop::Watchpoint(w.id, old, now));
+                    }
+                }
+                executed += 1;
+                (until(chip8) || executed >= limit).then_some(Stop::Done)
+            },
+        );
+        match halt {
+            Halt::Stopped(stop) => stop,
+            Halt::Fault(error) => Stop::Fault(error),
+        }
+    }
+
+    fn report<W: Write>(
+        &self,
+        chip8: &CHIP8,
+        stop: Stop,
+        output: &mut W,
+    ) -> Result<(), Chip8Error> {
+        match stop {
+            Stop::Done => {}
+            Stop::Breakpoint(id, pc) => writeln!(output, "Breakpoint {} Hit At {:#06x}", id, pc)?,
+            Stop::Watchpoint(id, old, new) => writeln!(
+                output,
+                "Watchpoint {} Changed : {:x?} -> {:x?}",
+                id, old, new
+            )?,
+            Stop::Fault(error) => writeln!(output, "Stopped : {}", error)?,
+        }
+        write_disas(chip8, chip8.get_cpu().get_pc(), 1, output)
+    }
+}
+
+impl Default for Debugger {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn parse_number(text: &str) -> Option<u16> {
+    let text = text.trim();
+    if let Some(hex) = text.strip_prefix("0x").or_else(|| text.strip_prefix('#')) {
+        u16::from_str_radix(hex, 16).ok()
+    } else {
+        text.parse().ok()
+    }
+}
+
+fn word_at(chip8: &CHIP8, address: u16) -> Option<u16> {
+    let memory = chip8.get_memory();
+    let most_sig = memory.get(address as usize)?;
+    let lest_sig = memory.get(address as usize + 1)?;
+    Some((most_sig as u16) << 8 | lest_sig as u16)
+}
+
+fn is_call(chip8: &CHIP8) -> bool {
+    let cpu = chip8.get_cpu();
+    word_at(chip8, cpu.get_pc()).is_some_and(|word| matches!(cpu.decode(word), OpCode::CALL(_)))
+}
+
+fn write_regs<W: Write>(chip8: &CHIP8, output: &mut W) -> Result<(), Chip8Error> {
+    let cpu = chip8.get_cpu();
+    for (idx, value) in cpu.get_registers().iter().enumerate() {
+        write!(output, "V{:X}={:02x}", idx, value)?;
+        write!(output, "{}", if idx % 8 == 7 { "\n" } else { " " })?;
+    }
+    writeln!(
+        output,
+        "I={:#06x} PC={:#06x} SP={} DT={} ST={}",
+        cpu.get_i(),
+        cpu.get_pc(),
+        cpu.get_sp(),
+        cpu.get_delay(),
+        cpu.get_sound()
+    )?;
+    Ok(())
+}
+
+fn write_disas<W: Write>(
+    chip8: &CHIP8,
+    address: u16,
+    count: u16,
+    output: &mut W,
+) -> Result<(), Chip8Error> {
+    let cpu = chip8.get_cpu();
+    for n in 0..count {
+        let addr = address.wrapping_add(n.wrapping_mul(2));
+        let Some(word) = word_at(chip8, addr) else {
+            break;
+        };
+        let marker = if addr == cpu.get_pc() { "=>" } else { "  " };
+        writeln!(
+            output,
+            "{} {:#06x}: {:04x}  {}",
+            marker,
+            addr,
+            word,
+            cpu.decode(word)
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cpu::CPU, display::Display, keyboard::Keyboard, memory::Memory, quirks::Quirks};
+
+    fn session(chip8: &mut CHIP8, commands: &str) -> String {
+        let mut output = Vec::new();
+        Debugger::new()
+            .repl(chip8, commands.as_bytes(), &mut output)
+            .unwrap();
+        String::from_utf8(output).unwrap()
+    }
+
+    #[test]
+    fn watch_parses_single_addresses_and_ranges() {
+        assert_eq!(
+            WatchTarget::parse("0xFFFF"),
+            Some(WatchTarget::Memory(0xFFFF, 0xFFFF))
+        );
+        assert_eq!(
+            WatchTarget::parse("0x300..0x310"),
+            Some(WatchTarget::Memory(0x300, 0x30F))
+        );
+        assert_eq!(WatchTarget::parse("0x310..0x300"), None);
+        assert_eq!(WatchTarget::Memory(0xFFFF, 0xFFFF).to_string(), "0xffff");
+        assert_eq!(
+            WatchTarget::Memory(0xFFF0, 0xFFFF).to_string(),
+            "0xfff0..0x10000"
+        );
+    }
+
+    #[test]
+    fn next_steps_over_a_call_at_the_end_of_memory() {
+        let mut memory = Memory::with_capacity(Memory::get_xo_cap());
+        // CALL 0x300 at 0xFFFE, which returns by wrapping round to 0x0000
+        *memory.get_mut(0xFFFE).unwrap() = 0x23;
+        *memory.get_mut(0xFFFF).unwrap() = 0x00;
+        *memory.get_mut(0x0300).unwrap() = 0x00;
+        *memory.get_mut(0x0301).unwrap() = 0xEE;
+        let mut cpu = CPU::with_quirks(Quirks::xo_chip());
+        cpu.set_pc(0xFFFE);
+        let mut chip8 = CHIP8::create(cpu, memory, Display::new(), Keyboard::new());
+
+        let output = session(&mut chip8, "watch 0xffff\nnext\n");
+        assert!(output.contains("Watchpoint 1 On 0xffff"), "{}", output);
+        assert_eq!(chip8.get_cpu().get_pc(), 0x0000);
+        assert_eq!(chip8.get_cpu().get_sp(), 0);
+    }
+}
@@ -0,0 +1,265 @@
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt,
+};
+
+use crate::cpu::OpCode;
+
+// data bytes are listed this many to a line
+const DATA_WIDTH: usize = 4;
+
+// A ROM split into code and data by following every path the program can
+// take from its entry point. Bytes no path reaches are shown as data.
+pub struct Disassembly {
+    origin: u16,
+    rom: Vec<u8>,
+    // offsets into rom where an instruction starts
+    code: BTreeSet<usize>,
+    labels: BTreeMap<u16, String>,
+}
+
+pub fn disassemble(rom: &[u8], origin: u16) -> Disassembly {
+    let mut disassembly = Disassembly {
+        origin,
+        rom: rom.to_vec(),
+        code: BTreeSet::new(),
+        labels: BTreeMap::new(),
+    };
+    disassembly.trace();
+    disassembly
+}
+
+impl Disassembly {
+    pub fn is_code(&self, address: u16) -> bool {
+        self.offset(address)
+            .is_some_and(|offset| self.code.contains(&offset))
+    }
+
+    pub fn label(&self, address: u16) -> Option<&str> {
+        self.labels.get(&address).map(|label| label.as_str())
+    }
+
+    fn offset(&self, address: u16) -> Option<usize> {
+        let offset = address.checked_sub(self.origin)? as usize;
+        (offset < self.rom.len()).then_some(offset)
+    }
+
+    fn word(&self, offset: usize) -> Option<u16> {
+        let most_sig = *self.rom.get(offset)?;
+        let lest_sig = *self.rom.get(offset + 1)?;
+        Some((most_sig as u16) << 8 | lest_sig as u16)
+    }
+
+    // instructions are two bytes, except F000 NNNN which carries its address after it
+    fn size(op: &OpCode) -> usize {
+        match op {
+            OpCode::LONG_I => 4,
+            _ => 2,
+        }
+    }
+
+    fn trace(&mut self) {
+        let mut pending = vec![self.origin];
+        let mut calls = BTreeSet::new();
+        let mut jumps = BTreeSet::new();
+
+        while let Some(address) = pending.pop() {
+            let Some(offset) = self.offset(address) else {
+                continue;
+            };
+            if self.code.contains(&offset) {
+                continue;
+            }
+            let Some(word) = self.word(offset) else {
+                continue;
+            };
+            let op = OpCode::from(word);
+            if matches!(op, OpCode::NONE)
+                || (matches!(op, OpCode::LONG_I) && self.word(offset + 2).is_none())
+            {
+                continue;
+            }
+            self.code.insert(offset);
+
+            let next = address.wrapping_add(Self::size(&op) as u16);
+            match op {
+                OpCode::GOTO(target) => {
+                    jumps.insert(target);
+                    pending.push(target);
+                }
+                OpCode::CALL(target) => {
+                    calls.insert(target);
+                    pending.extend([target, next]);
+                }
+                // V0 is unknown, so follow the base of what is usually a jump table
+                OpCode::JUMP(target) => {
+                    jumps.insert(target);
+                    pending.push(target);
+                }
+                OpCode::RETURN | OpCode::EXIT | OpCode::HALT => {}
+                OpCode::EQ(..)
+                | OpCode::NEQ(..)
+                | OpCode::EQ_REG(..)
+                | OpCode::NEQ_REG(..)
+                | OpCode::KEY_P(_)
+                | OpCode::KEY_NP(_) => {
+                    // a skip steps over the whole of a following F000 NNNN
+                    let skipped = self
+                        .offset(next)
+                        .and_then(|offset| self.word(offset))
+                        .map_or(2, |word| Self::size(&OpCode::from(word)) as u16);
+                    pending.extend([next, next.wrapping_add(skipped)]);
+                }
+                _ => pending.push(next),
+            }
+        }
+
+        // targets outside the ROM keep their raw addresses
+        for target in jumps {
+            if self.offset(target).is_some() {
+                self.labels.insert(target, format!("label_{:03X}", target));
+            }
+        }
+        for target in calls {
+            if self.offset(target).is_some() {
+                self.labels.insert(target, format!("sub_{:03X}", target));
+            }
+        }
+    }
+
+    fn target(&self, address: u16) -> String {
+        match self.label(address) {
+            Some(label) => label.to_string(),
+            None => format!("{:#05X}", address),
+        }
+    }
+
+    fn mnemonic(&self, op: &OpCode, offset: usize) -> String {
+        match *op {
+            OpCode::GOTO(addr) => format!("JP {}", self.target(addr)),
+            OpCode::CALL(addr) => format!("CALL {}", self.target(addr)),
+            OpCode::JUMP(addr) => format!("JP V0, {}", self.target(addr)),
+            OpCode::LONG_I => format!("LD I, LONG {:#06X}", self.word(offset + 2).unwrap_or(0)),
+            _ => op.to_string(),
+        }
+    }
+}
+
+impl fmt::Display for Disassembly {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut offset = 0;
+        while offset < self.rom.len() {
+            let address = self.origin.wrapping_add(offset as u16);
+            if let Some(label) = self.label(address) {
+                writeln!(f, "{}:", label)?;
+            }
+
+            if self.code.contains(&offset) {
+                let word = self.word(offset).unwrap_or(0);
+                let op = OpCode::from(word);
+                let size = Self::size(&op);
+                let raw: String = self.rom[offset..offset + size]
+                    .chunks(2)
+                    .map(|pair| format!("{:02X}{:02X}", pair[0], pair[1]))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                writeln!(
+                    f,
+                    "{:#05X}  {:<9}  {}",
+                    address,
+                    raw,
+                    self.mnemonic(&op, offset)
+                )?;
+                offset += size;
+                continue;
+            }
+
+            // a run of data ends at the next instruction or label
+            let mut end = offset + 1;
+            while end < self.rom.len()
+                && end - offset < DATA_WIDTH
+                && !self.code.contains(&end)
+                && self.label(self.origin.wrapping_add(end as u16)).is_none()
+            {
+                end += 1;
+            }
+            let bytes = &self.rom[offset..end];
+            let raw: String = bytes.iter().map(|b| format!("{:02X}", b)).collect();
+            let listed: Vec<String> = bytes.iter().map(|b| format!("{:#04X}", b)).collect();
+            writeln!(f, "{:#05X}  {:<9}  DB {}", address, raw, listed.join(", "))?;
+            offset = end;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[rustfmt::skip]
+    const ROM: [u8; 20] = [
+        0x63, 0x1F,             // LD V3, 0x1F
+        0xA2, 0x0E,             // LD I, 0x20E
+        0x22, 0x0A,             // CALL 0x20A
+        0x12, 0x06,             // JP 0x206
+        0xF0, 0x90,             // never reached
+        0xD0, 0x15,             // DRW V0, V1, 5
+        0x00, 0xEE,             // RET
+        0xF0, 0x90, 0xF0, 0x90, // sprite
+        0xF0, 0x00,             // unused
+    ];
+
+    #[test]
+    fn code_is_split_from_data_by_following_the_program() {
+        let disassembly = disassemble(&ROM, 0x200);
+        for address in [0x200, 0x202, 0x204, 0x206, 0x20A, 0x20C] {
+            assert!(disassembly.is_code(address), "{:#05X}", address);
+        }
+        // skipped over by the jump, pointed at by I, and past the end
+        for address in [0x208, 0x20E, 0x212, 0x214] {
+            assert!(!disassembly.is_code(address), "{:#05X}", address);
+        }
+        assert_eq!(disassembly.label(0x20A), Some("sub_20A"));
+        assert_eq!(disassembly.label(0x206), Some("label_206"));
+        assert_eq!(disassembly.label(0x20E), None);
+
+        let listing = disassembly.to_string();
+        let expected = "\
+0x200  631F       LD V3, 0x1F
+0x202  A20E       LD I, 0x20E
+0x204  220A       CALL sub_20A
+label_206:
+0x206  1206       JP label_206
+0x208  F090       DB 0xF0, 0x90
+sub_20A:
+0x20A  D015       DRW V0, V1, 5
+0x20C  00EE       RET
+0x20E  F090F090   DB 0xF0, 0x90, 0xF0, 0x90
+0x212  F000       DB 0xF0, 0x00
+";
+        assert_eq!(listing, expected);
+    }
+
+    #[test]
+    fn skips_step_over_long_loads() {
+        #[rustfmt::skip]
+        let rom = [
+            0x30, 0x00,             // SE V0, 0
+            0xF0, 0x00, 0x02, 0x0A, // LD I, LONG 0x20A
+            0x13, 0x00,             // JP 0x300, outside the ROM
+            0x00, 0x00,             // data
+            0x55, 0xAA,             // sprite
+        ];
+        let disassembly = disassemble(&rom, 0x200);
+        assert!(disassembly.is_code(0x202));
+        assert!(!disassembly.is_code(0x204));
+        assert!(disassembly.is_code(0x206));
+        assert!(!disassembly.is_code(0x20A));
+        assert_eq!(disassembly.label(0x300), None);
+
+        let listing = disassembly.to_string();
+        assert!(listing.contains("0x202  F000 020A  LD I, LONG 0x020A\n"));
+        assert!(listing.contains("0x206  1300       JP 0x300\n"));
+    }
+}
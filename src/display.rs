@@ -1,104 +1,241 @@
-use sdl2::{
-    event::EventPollIterator, pixels::Color, rect::Rect, render::Canvas, video::Window, EventPump,
+use crate::{
+    error::Chip8Error,
+    savestate::{self, StateReader, StateWriter},
 };
 
-const SIZE: (u32, u32) = (64, 32);
-const RESOLUTION: u32 = 16;
+pub const LORES: (u32, u32) = (64, 32);
+pub const HIRES: (u32, u32) = (128, 64);
 
-const BG: Color = Color::RGB(200, 100, 150);
-const FG: Color = Color::RGB(80, 80, 200);
+// XO-CHIP has two bitplanes, each pixel stores one bit per plane
+pub const PLANES: u8 = 0b11;
 
-pub struct Display {
-    canvas: Canvas<Window>,
-    event_pump: EventPump,
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Palette {
+    // indexed by the plane bits of a pixel: off, plane 1, plane 2, both
+    pub colors: [(u8, u8, u8); 4],
+}
 
-    buffer: [bool; SIZE.0 as usize * SIZE.1 as usize],
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            colors: [(200, 100, 150), (80, 80, 200), (240, 200, 80), (30, 30, 60)],
+        }
+    }
+}
 
-    key: Option<u8>,
+#[derive(Clone)]
+pub struct Display {
+    width: u32,
+    height: u32,
+    buffer: Vec<u8>,
+    planes: u8,
+
+    dirty: bool,
 }
 
 impl Display {
-    pub fn new(title: &str) -> Self {
-        let sdl_context = sdl2::init().unwrap();
-        let video_subsystem = sdl_context.video().unwrap();
-        let window = video_subsystem
-            .window(title, SIZE.0 * RESOLUTION, SIZE.1 * RESOLUTION)
-            .position_centered()
-            .build()
-            .unwrap();
-        let canvas = window.into_canvas().build().unwrap();
-        let event_pump = sdl_context.event_pump().unwrap();
-
+    pub fn new() -> Self {
         Self {
-            canvas: canvas,
-            event_pump: event_pump,
-            key: None,
-            buffer: [false; SIZE.0 as usize * SIZE.1 as usize],
+            width: LORES.0,
+            height: LORES.1,
+            buffer: vec![0; LORES.0 as usize * LORES.1 as usize],
+            planes: 0b01,
+            dirty: true,
         }
     }
 
     pub fn clear_dispaly(&mut self) {
-        self.canvas.set_draw_color(BG);
-        self.canvas.clear();
-        self.canvas.present();
-    }
-
-    pub fn dump_buffer(&mut self) {
-        for y in 0..SIZE.1 {
-            for x in 0..SIZE.0 {
-                print!(
-                    "{}",
-                    if self.buffer[y as usize * SIZE.0 as usize + x as usize] {
-                        "1"
-                    } else {
-                        "0"
-                    }
-                )
+        for pixel in self.buffer.iter_mut() {
+            *pixel &= !self.planes;
+        }
+        self.dirty = true;
+    }
+
+    pub fn set_hires(&mut self, hires: bool) {
+        let (width, height) = if hires { HIRES } else { LORES };
+        self.width = width;
+        self.height = height;
+        self.buffer = vec![0; width as usize * height as usize];
+        self.dirty = true;
+    }
+
+    pub fn is_hires(&self) -> bool {
+        (self.width, self.height) == HIRES
+    }
+
+    pub fn set_planes(&mut self, planes: u8) {
+        self.planes = planes & PLANES;
+    }
+
+    pub fn get_planes(&self) -> u8 {
+        self.planes
+    }
+
+    pub fn dump_buffer(&self) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                print!("{}", self.get_color(x, y))
             }
             println!();
         }
     }
 
-    pub fn draw_suqare(&mut self, x: u8, y: u8, color: bool) -> bool {
-        let x = ((x - 1) as u32 % SIZE.0) as u8;
-        let y = (y as u32 % SIZE.1) as u8;
+    // XORs one sprite pixel onto a plane, returning true when it turned a
+    // lit pixel off. Coordinates wrap, callers that clip do so beforehand.
+    pub fn draw_suqare(&mut self, x: u32, y: u32, plane: u8, color: bool) -> bool {
+        if !color {
+            return false;
+        }
+        let idx = (x % self.width) as usize + (y % self.height) as usize * self.width as usize;
+
+        let erased = self.buffer[idx] & plane != 0;
+        self.buffer[idx] ^= plane;
+        self.dirty = true;
+
+        erased
+    }
+
+    pub fn scroll_down(&mut self, n: u32) {
+        self.shift(0, n as i32);
+    }
+
+    pub fn scroll_right(&mut self, n: u32) {
+        self.shift(n as i32, 0);
+    }
+
+    pub fn scroll_left(&mut self, n: u32) {
+        self.shift(-(n as i32), 0);
+    }
+
+    // moves the selected planes by (dx, dy), filling the gap with unlit pixels
+    fn shift(&mut self, dx: i32, dy: i32) {
+        let (width, height) = (self.width as i32, self.height as i32);
+        let source = self.buffer.clone();
+        for y in 0..height {
+            for x in 0..width {
+                let (sx, sy) = (x - dx, y - dy);
+                let moved = if (0..width).contains(&sx) && (0..height).contains(&sy) {
+                    source[(sx + sy * width) as usize] & self.planes
+                } else {
+                    0
+                };
+                let pixel = &mut self.buffer[(x + y * width) as usize];
+                *pixel = (*pixel & !self.planes) | moved;
+            }
+        }
+        self.dirty = true;
+    }
+
+    pub fn get_pixel(&self, x: u32, y: u32) -> bool {
+        self.get_color(x, y) != 0
+    }
+
+    // the plane bits of a pixel, an index into a Palette
+    pub fn get_color(&self, x: u32, y: u32) -> u8 {
+        self.buffer[x as usize + y as usize * self.width as usize]
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
 
-        let over = self.buffer[x as usize + y as usize * SIZE.0 as usize] == color;
+    // returns whether the framebuffer changed since the last call,
+    // so frontends only redraw when there is something new to show
+    pub fn take_dirty(&mut self) -> bool {
+        std::mem::replace(&mut self.dirty, false)
+    }
 
-        // println!(
-        //     "X: {} Y: {} | {} ^ {} = {}",
-        //     x,
-        //     y,
-        //     self.buffer[(x + y * SIZE.0 as u8) as usize],
-        //     color,
-        //     self.buffer[(x + y * SIZE.0 as u8) as usize] ^ color
-        // );
+    pub(crate) fn write_state(&self, state: &mut StateWriter) {
+        state.u32(self.width);
+        state.u32(self.height);
+        state.u8(self.planes);
+        state.bytes(&self.buffer);
+    }
 
-        self.buffer[x as usize + y as usize * SIZE.0 as usize] ^= color;
+    // restoring always redraws, whatever was on screen before
+    pub(crate) fn read_state(&mut self, state: &mut StateReader) -> Result<(), Chip8Error> {
+        let width = state.u32()?;
+        let height = state.u32()?;
+        if (width, height) != LORES && (width, height) != HIRES {
+            return Err(savestate::invalid("Unknown Display Resolution"));
+        }
+        self.width = width;
+        self.height = height;
+        self.planes = state.u8()? & PLANES;
+        self.buffer = vec![0; width as usize * height as usize];
+        state.array(&mut self.buffer)?;
+        self.dirty = true;
+        Ok(())
+    }
+}
 
-        self.canvas
-            .set_draw_color(if self.buffer[x as usize + y as usize * SIZE.0 as usize] {
-                FG
-            } else {
-                BG
-            });
-        self.canvas
-            .fill_rect(Rect::new(
-                x as i32 * RESOLUTION as i32,
-                y as i32 * RESOLUTION as i32,
-                RESOLUTION as u32,
-                RESOLUTION as u32,
-            ))
-            .expect("Could Not Draw Rectangle");
+impl Default for Display {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lit(display: &Display) -> Vec<(u32, u32)> {
+        let (width, height) = display.size();
+        (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .filter(|(x, y)| display.get_pixel(*x, *y))
+            .collect()
+    }
+
+    #[test]
+    fn hires_resizes_and_clears() {
+        let mut display = Display::new();
+        display.draw_suqare(3, 3, 0b01, true);
+        display.set_hires(true);
+        assert_eq!(display.size(), HIRES);
+        assert!(display.is_hires());
+        assert!(lit(&display).is_empty());
+
+        display.draw_suqare(127, 63, 0b01, true);
+        assert_eq!(lit(&display), [(127, 63)]);
+        display.set_hires(false);
+        assert_eq!(display.size(), LORES);
+        assert!(lit(&display).is_empty());
+    }
 
-        over
+    #[test]
+    fn scroll_down_drops_the_bottom_rows() {
+        let mut display = Display::new();
+        display.draw_suqare(5, 0, 0b01, true);
+        display.draw_suqare(6, 30, 0b01, true);
+        display.scroll_down(2);
+        assert_eq!(lit(&display), [(5, 2)]);
     }
 
-    pub fn present(&mut self) {
-        self.canvas.present();
+    #[test]
+    fn scroll_sideways_by_four() {
+        let mut display = Display::new();
+        display.set_hires(true);
+        display.draw_suqare(0, 1, 0b01, true);
+        display.draw_suqare(126, 2, 0b01, true);
+        display.scroll_right(4);
+        assert_eq!(lit(&display), [(4, 1)]);
+
+        display.draw_suqare(2, 3, 0b01, true);
+        display.scroll_left(4);
+        assert_eq!(lit(&display), [(0, 1)]);
     }
 
-    pub fn events(&mut self) -> EventPollIterator {
-        self.event_pump.poll_iter()
+    #[test]
+    fn scrolling_moves_only_the_selected_planes() {
+        let mut display = Display::new();
+        display.draw_suqare(0, 0, 0b01, true);
+        display.draw_suqare(0, 0, 0b10, true);
+        display.set_planes(0b10);
+        display.scroll_down(1);
+        assert_eq!(
+            (display.get_color(0, 0), display.get_color(0, 1)),
+            (0b01, 0b10)
+        );
     }
 }
@@ -0,0 +1,85 @@
+use std::{error::Error, fmt, io};
+
+#[derive(Debug)]
+pub enum Chip8Error {
+    StackOverflow { pc: u16 },
+    StackUnderflow { pc: u16 },
+    OutOfBounds { address: usize, pc: u16 },
+    UnknownOpcode { opcode: u16, pc: u16 },
+    UnimplementedRoutine { address: u16, pc: u16 },
+    RomTooLarge { size: usize, capacity: usize },
+    LoadAddress { address: u16, capacity: usize },
+    Keymap { line: usize, reason: String },
+    // location is file:line
+    Assembly { location: String, reason: String },
+    SaveState { reason: String },
+    Font { reason: String },
+    Archive { reason: String },
+    // the window, input or sound could not be set up
+    Frontend { reason: String },
+    Io(io::Error),
+    // the program ran 00FD
+    Exit { pc: u16 },
+    // not a fault, the frontend asked the machine to stop
+    Quit,
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chip8Error::StackOverflow { pc } => write!(f, "Stack Overflow At {:#06x}", pc),
+            Chip8Error::StackUnderflow { pc } => write!(f, "Stack Underflow At {:#06x}", pc),
+            Chip8Error::OutOfBounds { address, pc } => write!(
+                f,
+                "Memory Address {:#06x} Is Out Of Bounds At {:#06x}",
+                address, pc
+            ),
+            Chip8Error::UnknownOpcode { opcode, pc } => {
+                write!(f, "Unknown Opcode {:#06x} At {:#06x}", opcode, pc)
+            }
+            Chip8Error::UnimplementedRoutine { address, pc } => write!(
+                f,
+                "Machine Routine {:#06x} Is Not Implemented, Called At {:#06x}",
+                address, pc
+            ),
+            Chip8Error::RomTooLarge { size, capacity } => write!(
+                f,
+                "ROM Is {} Bytes But Only {} Bytes Are Available",
+                size, capacity
+            ),
+            Chip8Error::LoadAddress { address, capacity } => write!(
+                f,
+                "Load Address {:#06x} Is Past The End Of {} Bytes Of Memory",
+                address, capacity
+            ),
+            Chip8Error::Keymap { line, reason } => {
+                write!(f, "Keymap Error On Line {} : {}", line, reason)
+            }
+            Chip8Error::Assembly { location, reason } => {
+                write!(f, "Assembly Error At {} : {}", location, reason)
+            }
+            Chip8Error::SaveState { reason } => write!(f, "Save State Error : {}", reason),
+            Chip8Error::Font { reason } => write!(f, "Font Error : {}", reason),
+            Chip8Error::Archive { reason } => write!(f, "Archive Error : {}", reason),
+            Chip8Error::Frontend { reason } => write!(f, "Frontend Error : {}", reason),
+            Chip8Error::Io(error) => write!(f, "I/O Error : {}", error),
+            Chip8Error::Exit { pc } => write!(f, "Program Exited At {:#06x}", pc),
+            Chip8Error::Quit => write!(f, "Program Quit"),
+        }
+    }
+}
+
+impl Error for Chip8Error {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            Chip8Error::Io(error) => Some(error),
+            _ => None,
+        }
+    }
+}
+
+impl From<io::Error> for Chip8Error {
+    fn from(error: io::Error) -> Self {
+        Chip8Error::Io(error)
+    }
+}
@@ -0,0 +1,264 @@
+use std::{fs, path::Path};
+
+use crate::error::Chip8Error;
+
+// where interpreters traditionally keep the font, below the program
+pub const DEFAULT_ADDRESS: u16 = 0x000;
+
+const SMALL_SIZE: usize = 5;
+const BIG_SIZE: usize = 10;
+
+// as in the COSMAC VIP monitor ROM, B and D are narrower than the rest
+#[rustfmt::skip]
+const COSMAC_VIP: [u8; 80] = [
+    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
+    0x60, 0x20, 0x20, 0x20, 0x70, // 1
+    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
+    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
+    0xA0, 0xA0, 0xF0, 0x20, 0x20, // 4
+    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
+    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
+    0xF0, 0x10, 0x10, 0x10, 0x10, // 7
+    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
+    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
+    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
+    0xF0, 0x50, 0x70, 0x50, 0xF0, // B
+    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
+    0xF0, 0x50, 0x50, 0x50, 0xF0, // D
+    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
+    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
+];
+
+#[rustfmt::skip]
+const ETI_660: [u8; 80] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x40, 0x40, 0x40, 0x40, 0x40, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+#[rustfmt::skip]
+const DREAM_6800: [u8; 80] = [
+    0xE0, 0xA0, 0xA0, 0xA0, 0xE0, // 0
+    0x40, 0xC0, 0x40, 0x40, 0xE0, // 1
+    0xE0, 0x20, 0xE0, 0x80, 0xE0, // 2
+    0xE0, 0x20, 0xE0, 0x20, 0xE0, // 3
+    0x80, 0xA0, 0xA0, 0xE0, 0x20, // 4
+    0xE0, 0x80, 0xE0, 0x20, 0xE0, // 5
+    0xE0, 0x80, 0xE0, 0xA0, 0xE0, // 6
+    0xE0, 0x20, 0x20, 0x20, 0x20, // 7
+    0xE0, 0xA0, 0xE0, 0xA0, 0xE0, // 8
+    0xE0, 0xA0, 0xE0, 0x20, 0xE0, // 9
+    0xE0, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xE0, 0xA0, 0xC0, // B
+    0xE0, 0x80, 0x80, 0x80, 0xE0, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xE0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+#[rustfmt::skip]
+const FISH_N_CHIPS: [u8; 80] = [
+    0x60, 0xA0, 0xA0, 0xA0, 0xC0, // 0
+    0x40, 0xC0, 0x40, 0x40, 0xE0, // 1
+    0xC0, 0x20, 0x40, 0x80, 0xE0, // 2
+    0xC0, 0x20, 0x40, 0x20, 0xC0, // 3
+    0x20, 0xA0, 0xE0, 0x20, 0x20, // 4
+    0xE0, 0x80, 0xC0, 0x20, 0xC0, // 5
+    0x40, 0x80, 0xC0, 0xA0, 0x40, // 6
+    0xE0, 0x20, 0x60, 0x40, 0x40, // 7
+    0x40, 0xA0, 0x40, 0xA0, 0x40, // 8
+    0x40, 0xA0, 0x60, 0x20, 0x40, // 9
+    0x40, 0xA0, 0xE0, 0xA0, 0xA0, // A
+    0xC0, 0xA0, 0xC0, 0xA0, 0xC0, // B
+    0x60, 0x80, 0x80, 0x80, 0x60, // C
+    0xC0, 0xA0, 0xA0, 0xA0, 0xC0, // D
+    0xE0, 0x80, 0xC0, 0x80, 0xE0, // E
+    0xE0, 0x80, 0xC0, 0x80, 0x80, // F
+];
+
+// SUPER-CHIP 8x10 glyphs, 0-9 as shipped with SCHIP 1.1 plus A-F
+#[rustfmt::skip]
+const SCHIP_BIG: [u8; 160] = [
+    0x3C, 0x7E, 0xE7, 0xC3, 0xC3, 0xC3, 0xC3, 0xE7, 0x7E, 0x3C, // 0
+    0x18, 0x38, 0x58, 0x18, 0x18, 0x18, 0x18, 0x18, 0x18, 0x3C, // 1
+    0x3E, 0x7F, 0xC3, 0x06, 0x0C, 0x18, 0x30, 0x60, 0xFF, 0xFF, // 2
+    0x3C, 0x7E, 0xC3, 0x03, 0x0E, 0x0E, 0x03, 0xC3, 0x7E, 0x3C, // 3
+    0x06, 0x0E, 0x1E, 0x36, 0x66, 0xC6, 0xFF, 0xFF, 0x06, 0x06, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFC, 0xFE, 0x03, 0xC3, 0x7E, 0x3C, // 5
+    0x3E, 0x7C, 0xC0, 0xC0, 0xFC, 0xFE, 0xC3, 0xC3, 0x7E, 0x3C, // 6
+    0xFF, 0xFF, 0x03, 0x06, 0x0C, 0x18, 0x30, 0x60, 0x60, 0x60, // 7
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7E, 0x7E, 0xC3, 0xC3, 0x7E, 0x3C, // 8
+    0x3C, 0x7E, 0xC3, 0xC3, 0x7F, 0x3F, 0x03, 0x03, 0x3E, 0x7C, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+// The hex digits FX29 points I at, drawn as 4x5 glyphs, followed by the
+// 8x10 SUPER-CHIP ones FX30 uses. Both sets are written into memory
+// together, the big glyphs straight after the small ones.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Font {
+    small: [u8; 80],
+    big: [u8; 160],
+    address: u16,
+}
+
+impl Font {
+    pub fn cosmac_vip() -> Self {
+        Self::with_small(COSMAC_VIP)
+    }
+
+    pub fn eti_660() -> Self {
+        Self::with_small(ETI_660)
+    }
+
+    pub fn dream_6800() -> Self {
+        Self::with_small(DREAM_6800)
+    }
+
+    pub fn fish_n_chips() -> Self {
+        Self::with_small(FISH_N_CHIPS)
+    }
+
+    fn with_small(small: [u8; 80]) -> Self {
+        Self {
+            small,
+            big: SCHIP_BIG,
+            address: DEFAULT_ADDRESS,
+        }
+    }
+
+    pub fn preset(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "vip" | "cosmac" | "cosmac-vip" => Some(Self::cosmac_vip()),
+            "eti" | "eti-660" | "eti660" => Some(Self::eti_660()),
+            "dream" | "dream-6800" | "dream6800" => Some(Self::dream_6800()),
+            "fish" | "fish-n-chips" | "fishnchips" => Some(Self::fish_n_chips()),
+            _ => None,
+        }
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Chip8Error> {
+        Self::from_bytes(&fs::read(path)?)
+    }
+
+    // 80 bytes replace the small glyphs only, 240 bytes replace both sets
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Chip8Error> {
+        let mut font = Self::cosmac_vip();
+        match bytes.len() {
+            80 => font.small.copy_from_slice(bytes),
+            240 => {
+                font.small.copy_from_slice(&bytes[..80]);
+                font.big.copy_from_slice(&bytes[80..]);
+            }
+            len => {
+                return Err(Chip8Error::Font {
+                    reason: format!("A Font Is 80 Or 240 Bytes, Found {}", len),
+                })
+            }
+        }
+        Ok(font)
+    }
+
+    pub fn set_address(&mut self, address: u16) {
+        self.address = address;
+    }
+
+    pub fn get_address(&self) -> u16 {
+        self.address
+    }
+
+    // bytes the font takes up in memory
+    pub fn size(&self) -> usize {
+        self.small.len() + self.big.len()
+    }
+
+    // where FX29 finds the small glyph for a digit
+    pub fn small_glyph(&self, digit: u8) -> u16 {
+        self.address
+            .wrapping_add((digit & 0xF) as u16 * SMALL_SIZE as u16)
+    }
+
+    // where FX30 finds the big glyph for a digit
+    pub fn big_glyph(&self, digit: u8) -> u16 {
+        self.address
+            .wrapping_add(self.small.len() as u16 + (digit & 0xF) as u16 * BIG_SIZE as u16)
+    }
+
+    pub(crate) fn write(&self, buffer: &mut [u8]) -> Result<(), Chip8Error> {
+        let start = self.address as usize;
+        let Some(region) = buffer.get_mut(start..start + self.size()) else {
+            return Err(Chip8Error::Font {
+                reason: format!("A Font At {:#06x} Does Not Fit In Memory", start),
+            });
+        };
+        region[..self.small.len()].copy_from_slice(&self.small);
+        region[self.small.len()..].copy_from_slice(&self.big);
+        Ok(())
+    }
+}
+
+impl Default for Font {
+    fn default() -> Self {
+        Self::cosmac_vip()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn small(font: &Font, digit: usize) -> &[u8] {
+        &font.small[digit * SMALL_SIZE..(digit + 1) * SMALL_SIZE]
+    }
+
+    #[test]
+    fn cosmac_vip_glyphs_match_the_rom() {
+        let font = Font::cosmac_vip();
+        assert_eq!(small(&font, 0x2), [0xF0, 0x10, 0xF0, 0x80, 0xF0]);
+        assert_eq!(small(&font, 0xB), [0xF0, 0x50, 0x70, 0x50, 0xF0]);
+        assert_eq!(small(&font, 0xD), [0xF0, 0x50, 0x50, 0x50, 0xF0]);
+    }
+
+    #[test]
+    fn glyph_addresses_follow_the_font_address() {
+        let mut font = Font::default();
+        font.set_address(0x50);
+        assert_eq!(font.small_glyph(0xA), 0x50 + 50);
+        assert_eq!(font.big_glyph(0x1), 0x50 + 80 + 10);
+        // only the low nibble picks a digit
+        assert_eq!(font.small_glyph(0x1A), font.small_glyph(0xA));
+    }
+
+    #[test]
+    fn from_bytes_takes_80_or_240_bytes() {
+        let font = Font::from_bytes(&[0xAA; 80]).unwrap();
+        assert_eq!(small(&font, 0x3), [0xAA; 5]);
+        assert_eq!(font.big, SCHIP_BIG);
+
+        let font = Font::from_bytes(&[0x55; 240]).unwrap();
+        assert_eq!(font.big, [0x55; 160]);
+
+        assert!(matches!(
+            Font::from_bytes(&[0; 100]),
+            Err(Chip8Error::Font { .. })
+        ));
+    }
+}
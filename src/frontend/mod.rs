@@ -0,0 +1,35 @@
+use crate::{
+    display::Display,
+    error::Chip8Error,
+    keyboard::{KeyBoardEvent, Keyboard},
+};
+
+pub mod null;
+#[cfg(feature = "sdl")]
+pub mod sdl;
+pub mod wav;
+
+pub trait VideoSink {
+    fn present(&mut self, display: &Display);
+}
+
+pub trait InputSource {
+    // drains pending host events, updating the keypad as it goes
+    fn poll(&mut self, keyboard: &mut Keyboard) -> Vec<KeyBoardEvent>;
+
+    // blocks until the host produces an event
+    fn wait(&mut self, keyboard: &mut Keyboard) -> KeyBoardEvent;
+}
+
+pub trait AudioSink {
+    // called once per frame with whether the sound timer is running
+    fn set_tone(&mut self, playing: bool);
+
+    // XO-CHIP programs can replace the beep with a 128 bit sample pattern
+    fn set_pattern(&mut self, _pattern: &[u8; 16], _pitch: u8) {}
+
+    // called once the program is done, for sinks that write to a file
+    fn finish(&mut self) -> Result<(), Chip8Error> {
+        Ok(())
+    }
+}
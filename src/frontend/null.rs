@@ -0,0 +1,34 @@
+use crate::{
+    display::Display,
+    keyboard::{KeyBoardEvent, Keyboard},
+};
+
+use super::{AudioSink, InputSource, VideoSink};
+
+#[derive(Default)]
+pub struct NullVideo;
+
+impl VideoSink for NullVideo {
+    fn present(&mut self, _display: &Display) {}
+}
+
+#[derive(Default)]
+pub struct NullInput;
+
+impl InputSource for NullInput {
+    fn poll(&mut self, _keyboard: &mut Keyboard) -> Vec<KeyBoardEvent> {
+        Vec::new()
+    }
+
+    // there is nobody to wait for, so stepping just carries on
+    fn wait(&mut self, _keyboard: &mut Keyboard) -> KeyBoardEvent {
+        KeyBoardEvent::Next
+    }
+}
+
+#[derive(Default)]
+pub struct NullAudio;
+
+impl AudioSink for NullAudio {
+    fn set_tone(&mut self, _playing: bool) {}
+}
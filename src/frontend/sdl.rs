@@ -0,0 +1,214 @@
+use std::fmt;
+
+use sdl2::{
+    audio::{AudioCallback, AudioDevice, AudioSpecDesired},
+    event::Event,
+    pixels::Color,
+    rect::Rect,
+    render::Canvas,
+    video::Window,
+    EventPump, Sdl,
+};
+
+use crate::{
+    audio::{BeeperConfig, Tone},
+    display::{Display, Palette},
+    error::Chip8Error,
+    keyboard::{KeyBoardEvent, Keyboard},
+    keymap::{Hotkey, Keymap},
+};
+
+use super::{AudioSink, InputSource, VideoSink};
+
+pub struct SdlFrontend {
+    context: Sdl,
+}
+
+impl SdlFrontend {
+    pub fn new() -> Result<Self, Chip8Error> {
+        Ok(Self {
+            context: sdl2::init().map_err(sdl_error)?,
+        })
+    }
+
+    // scale is the size in window pixels of one CHIP-8 pixel at the display's current resolution
+    pub fn video(
+        &self,
+        title: &str,
+        display: &Display,
+        scale: u32,
+        palette: Palette,
+    ) -> Result<SdlVideo, Chip8Error> {
+        let (width, height) = display.size();
+
+        let video_subsystem = self.context.video().map_err(sdl_error)?;
+        let window = video_subsystem
+            .window(title, width * scale, height * scale)
+            .position_centered()
+            .build()
+            .map_err(sdl_error)?;
+        let mut canvas = window.into_canvas().build().map_err(sdl_error)?;
+
+        let (r, g, b) = palette.colors[0];
+        canvas.set_draw_color(Color::RGB(r, g, b));
+        canvas.clear();
+        canvas.present();
+
+        Ok(SdlVideo { canvas, palette })
+    }
+
+    pub fn input(&self, keymap: Keymap) -> Result<SdlInput, Chip8Error> {
+        Ok(SdlInput {
+            event_pump: self.context.event_pump().map_err(sdl_error)?,
+            keymap,
+        })
+    }
+
+    // fails when the host has no audio device, which need not stop the emulator
+    pub fn audio(&self, config: BeeperConfig) -> Result<SdlAudio, Chip8Error> {
+        let audio_subsystem = self.context.audio().map_err(sdl_error)?;
+        let desired = AudioSpecDesired {
+            freq: Some(config.sample_rate as i32),
+            channels: Some(1),
+            samples: None,
+        };
+        let device = audio_subsystem
+            .open_playback(None, &desired, |spec| {
+                Tone::new(BeeperConfig {
+                    sample_rate: spec.freq as u32,
+                    ..config
+                })
+            })
+            .map_err(sdl_error)?;
+        device.resume();
+
+        Ok(SdlAudio { device })
+    }
+}
+
+// SDL reports its failures as plain strings or its own error types
+fn sdl_error<E: fmt::Display>(error: E) -> Chip8Error {
+    Chip8Error::Frontend {
+        reason: error.to_string(),
+    }
+}
+
+pub struct SdlVideo {
+    canvas: Canvas<Window>,
+    palette: Palette,
+}
+
+impl VideoSink for SdlVideo {
+    fn present(&mut self, display: &Display) {
+        let (width, height) = display.size();
+        // the window keeps its size, so pixels shrink when the display switches to hi-res
+        let (window_width, _) = self.canvas.output_size().unwrap();
+        let scale = window_width / width;
+
+        let (r, g, b) = self.palette.colors[0];
+        self.canvas.set_draw_color(Color::RGB(r, g, b));
+        self.canvas.clear();
+
+        for y in 0..height {
+            for x in 0..width {
+                let color = display.get_color(x, y);
+                if color != 0 {
+                    let (r, g, b) = self.palette.colors[color as usize];
+                    self.canvas.set_draw_color(Color::RGB(r, g, b));
+                    self.canvas
+                        .fill_rect(Rect::new(
+                            (x * scale) as i32,
+                            (y * scale) as i32,
+                            scale,
+                            scale,
+                        ))
+                        .expect("Could Not Draw Rectangle");
+                }
+            }
+        }
+
+        self.canvas.present();
+    }
+}
+
+impl AudioCallback for Tone {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.fill(out);
+    }
+}
+
+pub struct SdlAudio {
+    device: AudioDevice<Tone>,
+}
+
+impl AudioSink for SdlAudio {
+    fn set_tone(&mut self, playing: bool) {
+        self.device.lock().set_playing(playing);
+    }
+
+    fn set_pattern(&mut self, pattern: &[u8; 16], pitch: u8) {
+        self.device.lock().set_pattern(pattern, pitch);
+    }
+}
+
+pub struct SdlInput {
+    event_pump: EventPump,
+    keymap: Keymap,
+}
+
+impl InputSource for SdlInput {
+    fn poll(&mut self, keyboard: &mut Keyboard) -> Vec<KeyBoardEvent> {
+        let events: Vec<KeyBoardEvent> = self
+            .event_pump
+            .poll_iter()
+            .filter_map(|event| map_event(&self.keymap, event))
+            .collect();
+        for event in &events {
+            keyboard.set_key(*event);
+        }
+        events
+    }
+
+    fn wait(&mut self, keyboard: &mut Keyboard) -> KeyBoardEvent {
+        loop {
+            if let Some(event) = map_event(&self.keymap, self.event_pump.wait_event()) {
+                keyboard.set_key(event);
+                return event;
+            }
+        }
+    }
+}
+
+fn map_event(keymap: &Keymap, event: Event) -> Option<KeyBoardEvent> {
+    match event {
+        Event::Quit { .. } => Some(KeyBoardEvent::Quit),
+        Event::KeyDown {
+            keycode: Some(code),
+            ..
+        } => {
+            let name = code.name();
+            match keymap.hotkey(&name) {
+                Some(Hotkey::Quit) => Some(KeyBoardEvent::Quit),
+                Some(Hotkey::Next) => Some(KeyBoardEvent::Next),
+                Some(Hotkey::QuickSave) => Some(KeyBoardEvent::SaveState),
+                Some(Hotkey::QuickLoad) => Some(KeyBoardEvent::LoadState),
+                Some(Hotkey::Rewind) => Some(KeyBoardEvent::Rewind(true)),
+                None => keymap.keypad_key(&name).map(KeyBoardEvent::KeyPressed),
+            }
+        }
+        Event::KeyUp {
+            keycode: Some(code),
+            ..
+        } => {
+            let name = code.name();
+            match keymap.hotkey(&name) {
+                Some(Hotkey::Rewind) => Some(KeyBoardEvent::Rewind(false)),
+                Some(_) => None,
+                None => keymap.keypad_key(&name).map(KeyBoardEvent::KeyReleased),
+            }
+        }
+        _ => None,
+    }
+}
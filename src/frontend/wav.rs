@@ -0,0 +1,178 @@
+use std::{
+    fs::File,
+    io::{BufWriter, Seek, SeekFrom, Write},
+    path::Path,
+};
+
+use crate::{
+    audio::{BeeperConfig, Tone},
+    error::Chip8Error,
+    scheduler::FRAME_RATE,
+};
+
+use super::AudioSink;
+
+const HEADER_SIZE: u32 = 44;
+
+// Records the beeper into a 16 bit mono WAV file, one frame of samples per
+// call to set_tone, so the output can be checked without a sound card.
+pub struct WavAudio {
+    tone: Tone,
+    writer: BufWriter<File>,
+    samples: u32,
+    // samples per frame rarely divide evenly, carry the remainder over
+    remainder: u32,
+    // the first write failure, reported by finish
+    error: Option<Chip8Error>,
+    finished: bool,
+}
+
+impl WavAudio {
+    pub fn create<P: AsRef<Path>>(path: P, config: BeeperConfig) -> Result<Self, Chip8Error> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write_header(&mut writer, config.sample_rate, 0)?;
+
+        Ok(Self {
+            tone: Tone::new(config),
+            writer,
+            samples: 0,
+            remainder: 0,
+            error: None,
+            finished: false,
+        })
+    }
+
+    fn write_frame(&mut self) -> Result<(), Chip8Error> {
+        let sample_rate = self.tone.get_config().sample_rate;
+        let count = (sample_rate + self.remainder) / FRAME_RATE;
+        self.remainder = (sample_rate + self.remainder) % FRAME_RATE;
+
+        let mut buffer = vec![0.0; count as usize];
+        self.tone.fill(&mut buffer);
+        for sample in buffer {
+            let sample = (sample.clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+            self.writer.write_all(&sample.to_le_bytes())?;
+        }
+        self.samples += count;
+        Ok(())
+    }
+}
+
+impl AudioSink for WavAudio {
+    fn set_tone(&mut self, playing: bool) {
+        self.tone.set_playing(playing);
+        if self.error.is_none() {
+            self.error = self.write_frame().err();
+        }
+    }
+
+    fn set_pattern(&mut self, pattern: &[u8; 16], pitch: u8) {
+        self.tone.set_pattern(pattern, pitch);
+    }
+
+    // patches the sizes into the header, the file is complete after this
+    fn finish(&mut self) -> Result<(), Chip8Error> {
+        self.finished = true;
+        if let Some(error) = self.error.take() {
+            return Err(error);
+        }
+        let sample_rate = self.tone.get_config().sample_rate;
+        self.writer.seek(SeekFrom::Start(0))?;
+        write_header(&mut self.writer, sample_rate, self.samples)?;
+        self.writer.seek(SeekFrom::End(0))?;
+        self.writer.flush()?;
+        Ok(())
+    }
+}
+
+// a recorder nobody finished still leaves a playable file, though any
+// error in writing it is lost
+impl Drop for WavAudio {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.finish();
+        }
+    }
+}
+
+fn write_header<W: Write>(
+    writer: &mut W,
+    sample_rate: u32,
+    samples: u32,
+) -> Result<(), Chip8Error> {
+    let data_size = samples * 2;
+
+    writer.write_all(b"RIFF")?;
+    writer.write_all(&(HEADER_SIZE - 8 + data_size).to_le_bytes())?;
+    writer.write_all(b"WAVE")?;
+
+    writer.write_all(b"fmt ")?;
+    writer.write_all(&16u32.to_le_bytes())?;
+    // PCM, mono
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&1u16.to_le_bytes())?;
+    writer.write_all(&sample_rate.to_le_bytes())?;
+    // byte rate, block align, bits per sample
+    writer.write_all(&(sample_rate * 2).to_le_bytes())?;
+    writer.write_all(&2u16.to_le_bytes())?;
+    writer.write_all(&16u16.to_le_bytes())?;
+
+    writer.write_all(b"data")?;
+    writer.write_all(&data_size.to_le_bytes())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::{
+        chip8::CHIP8, cpu::CPU, debug_levels::DebugLevels, display::Display, keyboard::Keyboard,
+        memory::Memory,
+    };
+
+    fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn records_the_sound_timer() {
+        let path = std::env::temp_dir().join(format!("chip8-beep-{}.wav", std::process::id()));
+        let config = BeeperConfig {
+            sample_rate: 8000,
+            ..BeeperConfig::default()
+        };
+
+        // LD V0, 3 / LD ST, V0 / JP 0x204
+        let program = [0x60, 0x03, 0xF0, 0x18, 0x12, 0x04];
+        let memory = Memory::from_bytes(&program).unwrap();
+        let mut chip8 = CHIP8::create(CPU::new(), memory, Display::new(), Keyboard::new());
+        chip8.set_audio(Box::new(WavAudio::create(&path, config).unwrap()));
+        for _ in 0..4 {
+            chip8.run_frame(&DebugLevels::default()).unwrap();
+        }
+        chip8.finish_audio().unwrap();
+
+        let wav = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        // 8000 / 60 samples a frame, the remainder carried into the third
+        let samples = [133, 133, 134, 133];
+        let data_size = samples.iter().sum::<usize>() as u32 * 2;
+        assert_eq!(&wav[..4], b"RIFF");
+        assert_eq!(u32_at(&wav, 4), HEADER_SIZE - 8 + data_size);
+        assert_eq!(u32_at(&wav, 24), 8000);
+        assert_eq!(u32_at(&wav, 40), data_size);
+        assert_eq!(wav.len() as u32, HEADER_SIZE + data_size);
+
+        // the timer runs out after two frames of tone
+        let data: Vec<i16> = wav[HEADER_SIZE as usize..]
+            .chunks(2)
+            .map(|pair| i16::from_le_bytes([pair[0], pair[1]]))
+            .collect();
+        let (tone, silence) = data.split_at(266);
+        assert!(tone.iter().all(|sample| *sample != 0));
+        assert!(silence.iter().all(|sample| *sample == 0));
+    }
+}
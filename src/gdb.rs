@@ -0,0 +1,622 @@
+use std::{
+    io::{self, ErrorKind, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+};
+
+use crate::{
+    chip8::CHIP8,
+    error::Chip8Error,
+    stepper::{Halt, Stepper},
+};
+
+// V0-VF, I, PC, SP, DT, ST in the order of the g packet
+const REGISTER_COUNT: usize = 21;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.chip8.core">
+    <reg name="v0" bitsize="8" regnum="0"/>
+    <reg name="v1" bitsize="8"/>
+    <reg name="v2" bitsize="8"/>
+    <reg name="v3" bitsize="8"/>
+    <reg name="v4" bitsize="8"/>
+    <reg name="v5" bitsize="8"/>
+    <reg name="v6" bitsize="8"/>
+    <reg name="v7" bitsize="8"/>
+    <reg name="v8" bitsize="8"/>
+    <reg name="v9" bitsize="8"/>
+    <reg name="va" bitsize="8"/>
+    <reg name="vb" bitsize="8"/>
+    <reg name="vc" bitsize="8"/>
+    <reg name="vd" bitsize="8"/>
+    <reg name="ve" bitsize="8"/>
+    <reg name="vf" bitsize="8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="8"/>
+    <reg name="dt" bitsize="8"/>
+    <reg name="st" bitsize="8"/>
+  </feature>
+</target>
+"#;
+
+// A byte stream a debugger talks to us over. interrupted is checked while
+// the target runs and should consume a pending break (0x03) byte; the
+// default never interrupts, which suits scripted clients.
+pub trait Connection: Read + Write {
+    fn interrupted(&mut self) -> io::Result<bool> {
+        Ok(false)
+    }
+}
+
+impl Connection for TcpStream {
+    fn interrupted(&mut self) -> io::Result<bool> {
+        self.set_nonblocking(true)?;
+        let mut byte = [0];
+        let peeked = match self.peek(&mut byte) {
+            Ok(n) => Ok(n == 1 && byte[0] == 0x03),
+            Err(e) if e.kind() == ErrorKind::WouldBlock => Ok(false),
+            Err(e) => Err(e),
+        };
+        self.set_nonblocking(false)?;
+
+        if peeked? {
+            self.read_exact(&mut byte)?;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+}
+
+enum Resume {
+    Step,
+    Continue,
+}
+
+// A GDB remote serial protocol server. The target is halted between
+// packets; c and s drive it through CHIP8::step until a breakpoint,
+// a fault or a break from the client.
+pub struct GdbStub {
+    breakpoints: Vec<u16>,
+    no_ack: bool,
+    stepper: Stepper,
+}
+
+impl GdbStub {
+    pub fn new() -> Self {
+        Self {
+            breakpoints: Vec::new(),
+            no_ack: false,
+            stepper: Stepper::new(),
+        }
+    }
+
+    pub fn set_realtime(&mut self, realtime: bool) {
+        self.stepper.set_realtime(realtime);
+    }
+
+    // waits for a single debugger to connect, then serves it until it detaches
+    pub fn listen<A: ToSocketAddrs>(
+        &mut self,
+        chip8: &mut CHIP8,
+        addr: A,
+    ) -> Result<(), Chip8Error> {
+        let listener = TcpListener::bind(addr)?;
+        println!("Waiting For GDB On {}", listener.local_addr()?);
+
+        let (stream, peer) = listener.accept()?;
+        println!("GDB Connected From {}", peer);
+        stream.set_nodelay(true)?;
+        self.serve(chip8, stream)
+    }
+
+    pub fn serve<C: Connection>(
+        &mut self,
+        chip8: &mut CHIP8,
+        mut conn: C,
+    ) -> Result<(), Chip8Error> {
+        while let Some(packet) = self.receive(&mut conn)? {
+            let (cmd, args) = packet.split_at(packet.len().min(1));
+
+            let how = match cmd {
+                "c" | "s" => {
+                    resume_at(chip8, args);
+                    Some(if cmd == "c" {
+                        Resume::Continue
+                    } else {
+                        Resume::Step
+                    })
+                }
+                "v" => match args
+                    .strip_prefix("Cont;")
+                    .map(|action| action.as_bytes().first().copied())
+                {
+                    Some(Some(b's')) => Some(Resume::Step),
+                    Some(_) => Some(Resume::Continue),
+                    None => None,
+                },
+                _ => None,
+            };
+            if let Some(how) = how {
+                match self.resume(chip8, &mut conn, how)? {
+                    Ok(stop) => self.send(&mut conn, &stop)?,
+                    // the program is gone, so is the session
+                    Err(error) => {
+                        self.send(&mut conn, &stop_reply(&error))?;
+                        return match error {
+                            Chip8Error::Exit { .. } => Ok(()),
+                            error => Err(error),
+                        };
+                    }
+                }
+                continue;
+            }
+
+            let reply = match cmd {
+                "?" => "S05".to_string(),
+                "g" => read_registers(chip8),
+                "G" => write_registers(chip8, args),
+                "p" => usize::from_str_radix(args, 16)
+                    .ok()
+                    .and_then(|reg| read_register(chip8, reg))
+                    .unwrap_or_else(|| "E01".to_string()),
+                "P" => write_register(chip8, args),
+                "m" => read_memory(chip8, args),
+                "M" => write_memory(chip8, args),
+                "Z" | "z" => self.breakpoint(cmd == "Z", args),
+                "v" if args == "Cont?" => "vCont;c;s".to_string(),
+                "q" => query(args),
+                "Q" if args == "StartNoAckMode" => {
+                    self.send(&mut conn, "OK")?;
+                    self.no_ack = true;
+                    continue;
+                }
+                "H" => "OK".to_string(),
+                "D" => {
+                    self.send(&mut conn, "OK")?;
+                    return Ok(());
+                }
+                "k" => return Ok(()),
+                // an empty reply tells the client the packet is unsupported
+                _ => String::new(),
+            };
+
+            self.send(&mut conn, &reply)?;
+        }
+        Ok(())
+    }
+
+    // the outer Result is the connection failing, the inner one the program
+    // ending; faults only stop the target so the client can inspect them
+    fn resume<C: Connection>(
+        &mut self,
+        chip8: &mut CHIP8,
+        conn: &mut C,
+        how: Resume,
+    ) -> Result<Result<String, Chip8Error>, Chip8Error> {
+        let breakpoints = &self.breakpoints;
+        let halt = self.stepper.run(
+            chip8,
+            |chip8| {
+                breakpoints
+                    .contains(&chip8.get_cpu().get_pc())
+                    .then_some(Ok("S05"))
+            },
+            |_, frame| {
+                if frame {
+                    match conn.interrupted() {
+                        Ok(true) => return Some(Ok("S02")),
+                        Ok(false) => {}
+                        Err(error) => return Some(Err(error)),
+                    }
+                }
+                matches!(how, Resume::Step).then_some(Ok("S05"))
+            },
+        );
+
+        match halt {
+            Halt::Stopped(stop) => Ok(Ok(stop?.to_string())),
+            Halt::Fault(error @ (Chip8Error::Exit { .. } | Chip8Error::Quit)) => Ok(Err(error)),
+            Halt::Fault(error) => Ok(Ok(stop_reply(&error))),
+        }
+    }
+
+    fn breakpoint(&mut self, insert: bool, args: &str) -> String {
+        // type,addr,kind, where software (0) and hardware (1) breakpoints behave alike
+        let mut fields = args.split(',');
+        let (kind, addr) = (
+            fields.next(),
+            fields.next().map(|a| u16::from_str_radix(a, 16)),
+        );
+        match (kind, addr) {
+            (Some("0" | "1"), Some(Ok(addr))) => {
+                self.breakpoints.retain(|b| *b != addr);
+                if insert {
+                    self.breakpoints.push(addr);
+                }
+                "OK".to_string()
+            }
+            (Some("0" | "1"), _) => "E01".to_string(),
+            _ => String::new(),
+        }
+    }
+
+    // reads packets until a well formed one arrives, None when the client hangs up
+    fn receive<C: Connection>(&mut self, conn: &mut C) -> Result<Option<String>, Chip8Error> {
+        loop {
+            // skip acks and stray breaks between packets
+            match read_byte(conn)? {
+                Some(b'$') => {}
+                Some(_) => continue,
+                None => return Ok(None),
+            }
+
+            let mut data = Vec::new();
+            loop {
+                match read_byte(conn)? {
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                    None => return Ok(None),
+                }
+            }
+            let mut sum = [0; 2];
+            if conn.read_exact(&mut sum).is_err() {
+                return Ok(None);
+            }
+
+            let expected = std::str::from_utf8(&sum)
+                .ok()
+                .and_then(|sum| u8::from_str_radix(sum, 16).ok());
+            let valid = expected == Some(checksum(&data));
+            if !self.no_ack {
+                conn.write_all(if valid { b"+" } else { b"-" })?;
+            }
+            if valid {
+                return Ok(Some(String::from_utf8_lossy(&unescape(&data)).into_owned()));
+            }
+        }
+    }
+
+    fn send<C: Connection>(&self, conn: &mut C, data: &str) -> Result<(), Chip8Error> {
+        let data = escape(data.as_bytes());
+        loop {
+            conn.write_all(b"$")?;
+            conn.write_all(&data)?;
+            write!(conn, "#{:02x}", checksum(&data))?;
+            conn.flush()?;
+
+            if self.no_ack {
+                return Ok(());
+            }
+            match read_byte(conn)? {
+                Some(b'-') => continue,
+                _ => return Ok(()),
+            }
+        }
+    }
+}
+
+impl Default for GdbStub {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn read_byte<C: Connection>(conn: &mut C) -> Result<Option<u8>, Chip8Error> {
+    let mut byte = [0];
+    loop {
+        match conn.read(&mut byte) {
+            Ok(0) => return Ok(None),
+            Ok(_) => return Ok(Some(byte[0])),
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e.into()),
+        }
+    }
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn escape(data: &[u8]) -> Vec<u8> {
+    let mut escaped = Vec::with_capacity(data.len());
+    for byte in data {
+        if matches!(byte, b'#' | b'$' | b'}' | b'*') {
+            escaped.extend([b'}', byte ^ 0x20]);
+        } else {
+            escaped.push(*byte);
+        }
+    }
+    escaped
+}
+
+fn unescape(data: &[u8]) -> Vec<u8> {
+    let mut bytes = data.iter();
+    let mut unescaped = Vec::with_capacity(data.len());
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'}' => unescaped.extend(bytes.next().map(|b| b ^ 0x20)),
+            _ => unescaped.push(*byte),
+        }
+    }
+    unescaped
+}
+
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    if !text.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..text.len())
+        .step_by(2)
+        .map(|idx| u8::from_str_radix(text.get(idx..idx + 2)?, 16).ok())
+        .collect()
+}
+
+// registers travel little endian, as gdb expects from a target it has no byte order for
+fn register_bytes(chip8: &CHIP8, reg: usize) -> Option<Vec<u8>> {
+    let cpu = chip8.get_cpu();
+    match reg {
+        0..=15 => Some(vec![cpu.get_registers()[reg]]),
+        16 => Some(cpu.get_i().to_le_bytes().to_vec()),
+        17 => Some(cpu.get_pc().to_le_bytes().to_vec()),
+        18 => Some(vec![cpu.get_sp()]),
+        19 => Some(vec![cpu.get_delay()]),
+        20 => Some(vec![cpu.get_sound()]),
+        _ => None,
+    }
+}
+
+fn set_register_bytes(chip8: &mut CHIP8, reg: usize, bytes: &[u8]) -> Option<()> {
+    let cpu = chip8.get_cpu_mut();
+    let word = || Some(u16::from_le_bytes([*bytes.first()?, *bytes.get(1)?]));
+    match (reg, bytes.len()) {
+        (0..=15, 1) => cpu.set_register(reg, bytes[0]),
+        (16, 2) => cpu.set_i(word()?),
+        (17, 2) => cpu.set_pc(word()?),
+        // the stack pointer only moves through CALL and RETURN
+        (18, 1) if bytes[0] == cpu.get_sp() => {}
+        (19, 1) => cpu.set_delay(bytes[0]),
+        (20, 1) => cpu.set_sound(bytes[0]),
+        _ => return None,
+    }
+    Some(())
+}
+
+fn read_registers(chip8: &CHIP8) -> String {
+    (0..REGISTER_COUNT)
+        .filter_map(|reg| register_bytes(chip8, reg))
+        .map(|bytes| hex(&bytes))
+        .collect()
+}
+
+fn write_registers(chip8: &mut CHIP8, args: &str) -> String {
+    let Some(bytes) = unhex(args) else {
+        return "E01".to_string();
+    };
+
+    let mut offset = 0;
+    for reg in 0..REGISTER_COUNT {
+        let width = register_bytes(chip8, reg).map_or(0, |b| b.len());
+        let Some(value) = bytes.get(offset..offset + width) else {
+            return "E01".to_string();
+        };
+        // SP is carried in the packet but cannot be written, so leave it be
+        if reg != 18 && set_register_bytes(chip8, reg, value).is_none() {
+            return "E01".to_string();
+        }
+        offset += width;
+    }
+    "OK".to_string()
+}
+
+fn read_register(chip8: &CHIP8, reg: usize) -> Option<String> {
+    register_bytes(chip8, reg).map(|bytes| hex(&bytes))
+}
+
+fn write_register(chip8: &mut CHIP8, args: &str) -> String {
+    let written = args.split_once('=').and_then(|(reg, value)| {
+        let reg = usize::from_str_radix(reg, 16).ok()?;
+        set_register_bytes(chip8, reg, &unhex(value)?)
+    });
+    match written {
+        Some(()) => "OK".to_string(),
+        None => "E01".to_string(),
+    }
+}
+
+fn address_length(args: &str) -> Option<(usize, usize)> {
+    let (addr, len) = args.split_once(',')?;
+    Some((
+        usize::from_str_radix(addr, 16).ok()?,
+        usize::from_str_radix(len, 16).ok()?,
+    ))
+}
+
+fn read_memory(chip8: &CHIP8, args: &str) -> String {
+    let Some((addr, len)) = address_length(args) else {
+        return "E01".to_string();
+    };
+    // a read running off the end returns what could be read
+    let bytes: Vec<u8> = (addr..addr.saturating_add(len))
+        .map_while(|addr| chip8.get_memory().get(addr))
+        .collect();
+    if bytes.is_empty() && len > 0 {
+        return "E14".to_string();
+    }
+    hex(&bytes)
+}
+
+fn write_memory(chip8: &mut CHIP8, args: &str) -> String {
+    let parsed = args.split_once(':').and_then(|(range, data)| {
+        let (addr, len) = address_length(range)?;
+        Some((addr, unhex(data).filter(|data| data.len() == len)?))
+    });
+    let Some((addr, data)) = parsed else {
+        return "E01".to_string();
+    };
+
+    let memory = chip8.get_memory_mut();
+    if memory.get(addr + data.len().saturating_sub(1)).is_none() {
+        return "E14".to_string();
+    }
+    for (offset, byte) in data.into_iter().enumerate() {
+        if let Some(cell) = memory.get_mut(addr + offset) {
+            *cell = byte;
+        }
+    }
+    "OK".to_string()
+}
+
+fn resume_at(chip8: &mut CHIP8, args: &str) {
+    if let Ok(addr) = u16::from_str_radix(args, 16) {
+        chip8.get_cpu_mut().set_pc(addr);
+    }
+}
+
+fn query(args: &str) -> String {
+    match args.split_once(':').map_or(args, |(name, _)| name) {
+        "Supported" => "PacketSize=1000;qXfer:features:read+;QStartNoAckMode+".to_string(),
+        "Attached" => "1".to_string(),
+        "C" => "QC1".to_string(),
+        "fThreadInfo" => "m1".to_string(),
+        "sThreadInfo" => "l".to_string(),
+        "Xfer" => xfer_target(args),
+        _ => String::new(),
+    }
+}
+
+// qXfer:features:read:target.xml:offset,length
+fn xfer_target(args: &str) -> String {
+    let Some(range) = args.strip_prefix("Xfer:features:read:target.xml:") else {
+        return "E00".to_string();
+    };
+    let Some((offset, len)) = address_length(range) else {
+        return "E01".to_string();
+    };
+
+    let xml = TARGET_XML.as_bytes();
+    let start = offset.min(xml.len());
+    let end = offset.saturating_add(len).min(xml.len());
+    let marker = if end == xml.len() { "l" } else { "m" };
+    format!("{}{}", marker, String::from_utf8_lossy(&xml[start..end]))
+}
+
+fn stop_reply(error: &Chip8Error) -> String {
+    match error {
+        Chip8Error::Exit { .. } => "W00".to_string(),
+        Chip8Error::Quit => "X09".to_string(),
+        Chip8Error::UnknownOpcode { .. } | Chip8Error::UnimplementedRoutine { .. } => {
+            "S04".to_string()
+        }
+        Chip8Error::StackOverflow { .. }
+        | Chip8Error::StackUnderflow { .. }
+        | Chip8Error::OutOfBounds { .. } => "S0b".to_string(),
+        _ => "S06".to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, io::Cursor, rc::Rc};
+
+    use super::*;
+    use crate::{cpu::CPU, display::Display, keyboard::Keyboard, memory::Memory};
+
+    // a client that has already sent everything it will say
+    struct Script {
+        input: Cursor<Vec<u8>>,
+        output: Rc<RefCell<Vec<u8>>>,
+    }
+
+    impl Read for Script {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            self.input.read(buf)
+        }
+    }
+
+    impl Write for Script {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.output.borrow_mut().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    impl Connection for Script {}
+
+    // sends the packets without acks and returns the replies to them
+    fn session(chip8: &mut CHIP8, packets: &[&str]) -> Vec<String> {
+        let mut input = String::new();
+        for (idx, packet) in ["QStartNoAckMode"].iter().chain(packets).enumerate() {
+            input += &format!("${}#{:02x}", packet, checksum(packet.as_bytes()));
+            // the reply to QStartNoAckMode is still acked
+            if idx == 0 {
+                input += "+";
+            }
+        }
+        let output = Rc::new(RefCell::new(Vec::new()));
+        let script = Script {
+            input: Cursor::new(input.into_bytes()),
+            output: Rc::clone(&output),
+        };
+        GdbStub::new().serve(chip8, script).unwrap();
+
+        let output = String::from_utf8(output.take()).unwrap();
+        output
+            .split('$')
+            .skip(2)
+            .map(|reply| reply.rsplit_once('#').unwrap().0.to_string())
+            .collect()
+    }
+
+    #[test]
+    fn serves_a_session() {
+        // LD V0, 5 / ADD V0, 1 / JP 0x202
+        let program = [0x60, 0x05, 0x70, 0x01, 0x12, 0x02];
+        let mut cpu = CPU::new();
+        cpu.set_pc(0x200);
+        let memory = Memory::from_bytes(&program).unwrap();
+        let mut chip8 = CHIP8::create(cpu, memory, Display::new(), Keyboard::new());
+
+        let registers = "00".repeat(16) + "0000" + "0002" + "00" + "00" + "00";
+        let written = "ab".to_string() + &registers[2..];
+        let replies = session(
+            &mut chip8,
+            &[
+                "qSupported:multiprocess+",
+                "g",
+                &format!("G{}", written),
+                "p0",
+                "m200,6",
+                "M206,2:f165",
+                "m206,2",
+                "Z0,204,2",
+                "c",
+                "p11",
+                "z0,204,2",
+                "s",
+                "p11",
+                // FX65 with I at the last byte of memory runs off the end
+                "P10=ff0f",
+                "c206",
+                "D",
+            ],
+        );
+
+        assert!(replies[0].contains("PacketSize"), "{}", replies[0]);
+        assert_eq!(replies[1], registers);
+        assert_eq!(replies[2..5], ["OK", "ab", "600570011202"]);
+        assert_eq!(replies[5..7], ["OK", "f165"]);
+        // continue stops at the breakpoint, then step runs one instruction
+        assert_eq!(replies[7..10], ["OK", "S05", "0402"]);
+        assert_eq!(replies[10..13], ["OK", "S05", "0202"]);
+        assert_eq!(replies[13..], ["OK", "S0b", "OK"]);
+    }
+}
@@ -1,104 +1,146 @@
-use sdl2::{
-    event::{Event, EventPollIterator},
-    keyboard::Keycode,
+use crate::{
+    error::Chip8Error,
+    savestate::{StateReader, StateWriter},
 };
 
-use crate::display::Display;
-
 #[derive(PartialEq, Copy, Clone)]
 pub enum KeyBoardEvent {
     Quit,
     Next,
+    SaveState,
+    LoadState,
+    // the rewind hotkey went down (true) or up (false)
+    Rewind(bool),
     KeyPressed(u8),
+    KeyReleased(u8),
 }
 
+#[derive(Clone)]
 pub struct Keyboard {
-    key: Option<KeyBoardEvent>,
+    // one bit per keypad key, set while the key is held down
+    keys: u16,
+    // one bit per keypad key, set when a held key is let go
+    released: u16,
 }
 
 impl Keyboard {
     pub fn new() -> Self {
-        Self { key: None }
+        Self {
+            keys: 0,
+            released: 0,
+        }
     }
 
     pub fn is_key_pressed(&self, key: u8) -> bool {
-        if let Some(KeyBoardEvent::KeyPressed(k)) = self.key {
-            return k == key;
+        key < 16 && self.keys & (1 << key) != 0
+    }
+
+    // the lowest numbered key currently held, if any
+    pub fn pressed_key(&self) -> Option<u8> {
+        if self.keys == 0 {
+            None
         } else {
-            return false;
+            Some(self.keys.trailing_zeros() as u8)
         }
     }
 
-    pub fn clear_key(&mut self) {
-        self.key = None;
+    pub fn press(&mut self, key: u8) {
+        self.keys |= 1 << (key & 0xF);
     }
 
-    pub fn map_code(&mut self, code: Keycode) -> Option<KeyBoardEvent> {
-        self.key = match code {
-            Keycode::W => Some(KeyBoardEvent::Next),
-            Keycode::Num0 => Some(KeyBoardEvent::KeyPressed(0)),
-            Keycode::Num1 => Some(KeyBoardEvent::KeyPressed(1)),
-            Keycode::Num2 => Some(KeyBoardEvent::KeyPressed(2)),
-            Keycode::Num3 => Some(KeyBoardEvent::KeyPressed(3)),
-            Keycode::Num4 => Some(KeyBoardEvent::KeyPressed(4)),
-            Keycode::Num5 => Some(KeyBoardEvent::KeyPressed(5)),
-            Keycode::Num6 => Some(KeyBoardEvent::KeyPressed(6)),
-            Keycode::Num7 => Some(KeyBoardEvent::KeyPressed(7)),
-            Keycode::Num8 => Some(KeyBoardEvent::KeyPressed(8)),
-            Keycode::Num9 => Some(KeyBoardEvent::KeyPressed(9)),
-            Keycode::A => Some(KeyBoardEvent::KeyPressed(10)),
-            Keycode::B => Some(KeyBoardEvent::KeyPressed(11)),
-            Keycode::C => Some(KeyBoardEvent::KeyPressed(12)),
-            Keycode::D => Some(KeyBoardEvent::KeyPressed(13)),
-            Keycode::E => Some(KeyBoardEvent::KeyPressed(14)),
-            Keycode::F => Some(KeyBoardEvent::KeyPressed(15)),
-            _ => None,
-        };
-        self.key
+    pub fn release(&mut self, key: u8) {
+        let bit = 1 << (key & 0xF);
+        self.released |= self.keys & bit;
+        self.keys &= !bit;
     }
 
-    pub fn map_event(&mut self, event: Event) -> Option<KeyBoardEvent> {
-        match event {
-            Event::Quit { .. }
-            | Event::KeyDown {
-                keycode: Some(Keycode::Escape),
-                ..
-            } => Some(KeyBoardEvent::Quit),
-            Event::KeyDown {
-                keycode: Some(Keycode::W),
-                ..
-            } => Some(KeyBoardEvent::Next),
-
-            Event::KeyDown {
-                keycode: Some(code),
-                ..
-            } => {
-                if let Some(key) = self.map_code(code) {
-                    Some(key)
-                } else {
-                    None
-                }
-            }
-            _ => None,
+    // the lowest numbered key released since the last clear_released
+    pub fn released_key(&self) -> Option<u8> {
+        if self.released == 0 {
+            None
+        } else {
+            Some(self.released.trailing_zeros() as u8)
         }
     }
 
-    pub fn get_events(&mut self, events: EventPollIterator) -> Vec<KeyBoardEvent> {
-        events.filter_map(|e| self.map_event(e)).collect()
+    pub fn clear_released(&mut self) {
+        self.released = 0;
     }
 
-    pub fn wait_key(&mut self, display: &mut Display) -> KeyBoardEvent {
-        loop {
-            for event in display.events() {
-                match self.map_event(event) {
-                    Some(a) => {
-                        return a;
-                    }
-                    _ => {
-                        // print!("{:#?}", event);
-                    }
-                }
-            }
+    pub fn set_key(&mut self, event: KeyBoardEvent) {
+        match event {
+            KeyBoardEvent::KeyPressed(key) => self.press(key),
+            KeyBoardEvent::KeyReleased(key) => self.release(key),
+            _ => {}
         }
     }
+
+    pub fn get_state(&self) -> u16 {
+        self.keys
+    }
+
+    // releases every key, e.g. when the window loses focus
+    pub fn clear_key(&mut self) {
+        self.keys = 0;
+    }
+
+    pub(crate) fn write_state(&self, state: &mut StateWriter) {
+        state.u16(self.keys);
+        state.u16(self.released);
+    }
+
+    pub(crate) fn read_state(&mut self, state: &mut StateReader) -> Result<(), Chip8Error> {
+        self.keys = state.u16()?;
+        self.released = state.u16()?;
+        Ok(())
+    }
+}
+
+impl Default for Keyboard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_held_until_released() {
+        let mut keyboard = Keyboard::new();
+        keyboard.set_key(KeyBoardEvent::KeyPressed(0x3));
+        keyboard.set_key(KeyBoardEvent::KeyPressed(0xC));
+        assert!(keyboard.is_key_pressed(0x3));
+        assert!(keyboard.is_key_pressed(0xC));
+        assert!(!keyboard.is_key_pressed(0x4));
+        assert_eq!(keyboard.get_state(), 1 << 0x3 | 1 << 0xC);
+        assert_eq!(keyboard.pressed_key(), Some(0x3));
+
+        keyboard.set_key(KeyBoardEvent::KeyReleased(0x3));
+        assert!(!keyboard.is_key_pressed(0x3));
+        assert_eq!(keyboard.pressed_key(), Some(0xC));
+        // values past the keypad are never pressed
+        assert!(!keyboard.is_key_pressed(0x13));
+    }
+
+    #[test]
+    fn releases_are_remembered_until_cleared() {
+        let mut keyboard = Keyboard::new();
+        // letting go of a key that was not held is not a release
+        keyboard.release(0x5);
+        assert_eq!(keyboard.released_key(), None);
+
+        keyboard.press(0x5);
+        keyboard.press(0x2);
+        keyboard.release(0x5);
+        keyboard.release(0x2);
+        assert_eq!(keyboard.released_key(), Some(0x2));
+        keyboard.clear_released();
+        assert_eq!(keyboard.released_key(), None);
+
+        keyboard.press(0x7);
+        keyboard.clear_key();
+        assert_eq!(keyboard.pressed_key(), None);
+    }
 }
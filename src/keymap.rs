@@ -0,0 +1,271 @@
+use std::{collections::HashMap, fs, path::Path};
+
+use crate::error::Chip8Error;
+
+// The COSMAC VIP keypad laid over the left hand side of a QWERTY keyboard
+//
+//   1 2 3 C        1 2 3 4
+//   4 5 6 D   <-   Q W E R
+//   7 8 9 E        A S D F
+//   A 0 B F        Z X C V
+const DEFAULT_KEYPAD: [(&str, u8); 16] = [
+    ("1", 0x1),
+    ("2", 0x2),
+    ("3", 0x3),
+    ("4", 0xC),
+    ("Q", 0x4),
+    ("W", 0x5),
+    ("E", 0x6),
+    ("R", 0xD),
+    ("A", 0x7),
+    ("S", 0x8),
+    ("D", 0x9),
+    ("F", 0xE),
+    ("Z", 0xA),
+    ("X", 0x0),
+    ("C", 0xB),
+    ("V", 0xF),
+];
+
+const DEFAULT_HOTKEYS: [(&str, Hotkey); 5] = [
+    ("ESCAPE", Hotkey::Quit),
+    ("F6", Hotkey::Next),
+    ("F5", Hotkey::QuickSave),
+    ("F9", Hotkey::QuickLoad),
+    ("BACKSPACE", Hotkey::Rewind),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Hotkey {
+    Quit,
+    Next,
+    QuickSave,
+    QuickLoad,
+    Rewind,
+}
+
+impl Hotkey {
+    fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "quit" => Some(Hotkey::Quit),
+            "next" | "step" => Some(Hotkey::Next),
+            "quicksave" | "save" => Some(Hotkey::QuickSave),
+            "quickload" | "load" => Some(Hotkey::QuickLoad),
+            "rewind" => Some(Hotkey::Rewind),
+            _ => None,
+        }
+    }
+}
+
+// Maps host key names (as SDL spells them, compared case-insensitively)
+// to keypad keys and to emulator hotkeys. The two sets never share a host key.
+#[derive(Clone, Debug)]
+pub struct Keymap {
+    keypad: HashMap<String, u8>,
+    hotkeys: HashMap<String, Hotkey>,
+}
+
+impl Keymap {
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, Chip8Error> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+
+    // Reads a config of the form
+    //
+    //   [keypad]
+    //   C = 4        # keypad key C on the host's 4 key
+    //   [hotkeys]
+    //   next = F6
+    //
+    // Anything not mentioned keeps its default binding.
+    pub fn parse(source: &str) -> Result<Self, Chip8Error> {
+        let mut keymap = Self::default();
+        let mut section = "";
+        // the last line each host key was bound on, to report clashes against
+        let mut bound_on = HashMap::new();
+
+        for (idx, line) in source.lines().enumerate() {
+            let line_number = idx + 1;
+            let error = |reason: String| Chip8Error::Keymap {
+                line: line_number,
+                reason,
+            };
+
+            let line = line.split('#').next().unwrap_or("").trim();
+            if line.is_empty() {
+                continue;
+            }
+            if let Some(name) = line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = match name.trim() {
+                    "keypad" => "keypad",
+                    "hotkeys" => "hotkeys",
+                    other => return Err(error(format!("Unknown Section [{}]", other))),
+                };
+                continue;
+            }
+
+            let (target, host) = line
+                .split_once('=')
+                .ok_or_else(|| error(format!("Expected `name = key`, Found `{}`", line)))?;
+            let (target, host) = (target.trim(), host.trim().to_ascii_uppercase());
+            if host.is_empty() {
+                return Err(error(format!("No Host Key Given For `{}`", target)));
+            }
+
+            match section {
+                "keypad" => {
+                    let key = u8::from_str_radix(target, 16)
+                        .ok()
+                        .filter(|key| *key < 16)
+                        .ok_or_else(|| error(format!("`{}` Is Not A Keypad Key", target)))?;
+                    keymap.keypad.retain(|_, bound| *bound != key);
+                    keymap.keypad.insert(host.clone(), key);
+                }
+                "hotkeys" => {
+                    let hotkey = Hotkey::from_name(target)
+                        .ok_or_else(|| error(format!("`{}` Is Not A Hotkey", target)))?;
+                    keymap.hotkeys.retain(|_, bound| *bound != hotkey);
+                    keymap.hotkeys.insert(host.clone(), hotkey);
+                }
+                _ => return Err(error("Binding Outside Of A Section".to_string())),
+            }
+            bound_on.insert(host, line_number);
+        }
+
+        // a later line may move a hotkey off a key the keypad has just
+        // taken, so clashes are only looked for once the whole file is read
+        let clash = keymap
+            .conflicts()
+            .map(|host| (bound_on.get(host).copied().unwrap_or(0), host))
+            .min();
+        if let Some((line, host)) = clash {
+            return Err(Chip8Error::Keymap {
+                line,
+                reason: format!("{} Is Bound To Both The Keypad And A Hotkey", host),
+            });
+        }
+
+        Ok(keymap)
+    }
+
+    pub fn keypad_key(&self, host: &str) -> Option<u8> {
+        self.keypad.get(&host.to_ascii_uppercase()).copied()
+    }
+
+    pub fn hotkey(&self, host: &str) -> Option<Hotkey> {
+        self.hotkeys.get(&host.to_ascii_uppercase()).copied()
+    }
+
+    fn conflicts(&self) -> impl Iterator<Item = &str> {
+        self.hotkeys
+            .keys()
+            .filter(|host| self.keypad.contains_key(*host))
+            .map(|host| host.as_str())
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self {
+            keypad: DEFAULT_KEYPAD
+                .iter()
+                .map(|(host, key)| (host.to_string(), *key))
+                .collect(),
+            hotkeys: DEFAULT_HOTKEYS
+                .iter()
+                .map(|(host, hotkey)| (host.to_string(), *hotkey))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rejected(source: &str) -> (usize, String) {
+        match Keymap::parse(source) {
+            Err(Chip8Error::Keymap { line, reason }) => (line, reason),
+            other => panic!("expected a keymap error, got {:?}", other.map(|_| ())),
+        }
+    }
+
+    #[test]
+    fn the_default_layout_is_the_left_hand_block() {
+        let keymap = Keymap::default();
+        let rows = ["1234", "QWER", "ASDF", "ZXCV"];
+        let keypad = [
+            [0x1, 0x2, 0x3, 0xC],
+            [0x4, 0x5, 0x6, 0xD],
+            [0x7, 0x8, 0x9, 0xE],
+            [0xA, 0x0, 0xB, 0xF],
+        ];
+        for (row, keys) in rows.iter().zip(keypad) {
+            for (host, key) in row.chars().zip(keys) {
+                assert_eq!(keymap.keypad_key(&host.to_string()), Some(key));
+            }
+        }
+        assert_eq!(keymap.keypad_key("q"), Some(0x4));
+        assert_eq!(keymap.keypad_key("P"), None);
+        assert_eq!(keymap.hotkey("Escape"), Some(Hotkey::Quit));
+        assert_eq!(keymap.hotkey("F5"), Some(Hotkey::QuickSave));
+        assert_eq!(keymap.hotkey("Q"), None);
+    }
+
+    #[test]
+    fn bindings_override_the_defaults() {
+        let keymap = Keymap::parse(
+            "# arrows for movement\n\
+             [keypad]\n\
+             5 = Up   # was W\n\
+             8 = down\n\
+             [hotkeys]\n\
+             step = N\n",
+        )
+        .unwrap();
+        assert_eq!(keymap.keypad_key("UP"), Some(0x5));
+        assert_eq!(keymap.keypad_key("Down"), Some(0x8));
+        // the old host keys are free again
+        assert_eq!(keymap.keypad_key("W"), None);
+        assert_eq!(keymap.keypad_key("S"), None);
+        assert_eq!(keymap.hotkey("n"), Some(Hotkey::Next));
+        assert_eq!(keymap.hotkey("F6"), None);
+        // everything not mentioned keeps its binding
+        assert_eq!(keymap.keypad_key("Q"), Some(0x4));
+        assert_eq!(keymap.hotkey("ESCAPE"), Some(Hotkey::Quit));
+    }
+
+    #[test]
+    fn a_hotkey_moved_later_in_the_file_is_not_a_clash() {
+        let keymap = Keymap::parse("[keypad]\nF = F5\n[hotkeys]\nsave = F7\n").unwrap();
+        assert_eq!(keymap.keypad_key("F5"), Some(0xF));
+        assert_eq!(keymap.hotkey("F5"), None);
+        assert_eq!(keymap.hotkey("F7"), Some(Hotkey::QuickSave));
+    }
+
+    #[test]
+    fn a_host_key_on_both_sides_is_a_clash() {
+        let (line, reason) = rejected("[keypad]\nF = F5\n");
+        assert_eq!(line, 2);
+        assert!(reason.contains("F5"), "{}", reason);
+
+        // Q is freed by the keypad section, W is not
+        let (line, reason) = rejected("[hotkeys]\nquit = Q\nrewind = W\n[keypad]\n4 = P\n");
+        assert_eq!(line, 3);
+        assert!(reason.contains('W'), "{}", reason);
+    }
+
+    #[test]
+    fn unknown_names_are_errors() {
+        assert_eq!(rejected("[keys]\n").0, 1);
+        assert!(rejected("[keys]\n").1.contains("Unknown Section"));
+        assert_eq!(rejected("[keypad]\nG = P\n").0, 2);
+        assert_eq!(rejected("[keypad]\n10 = P\n").0, 2);
+        assert!(rejected("[hotkeys]\npause = P\n")
+            .1
+            .contains("Not A Hotkey"));
+        assert!(rejected("[keypad]\n1 =\n").1.contains("No Host Key"));
+        assert!(rejected("[keypad]\n1 P\n").1.contains("Expected"));
+        assert!(rejected("1 = P\n").1.contains("Outside Of A Section"));
+    }
+}
@@ -0,0 +1,41 @@
+#![allow(clippy::upper_case_acronyms)]
+
+pub mod archive;
+pub mod assembler;
+pub mod audio;
+pub mod chip8;
+pub mod cpu;
+pub mod debug_levels;
+pub mod debugger;
+pub mod disasm;
+pub mod display;
+pub mod error;
+pub mod font;
+pub mod frontend;
+pub mod gdb;
+pub mod keyboard;
+pub mod keymap;
+pub mod memory;
+pub mod octo;
+pub mod quirks;
+pub mod rewind;
+pub mod savestate;
+pub mod scheduler;
+pub mod stack;
+pub mod stepper;
+
+pub use chip8::CHIP8;
+pub use cpu::{OpCode, CPU};
+pub use debug_levels::DebugLevels;
+pub use debugger::Debugger;
+pub use display::Display;
+pub use error::Chip8Error;
+pub use font::Font;
+pub use gdb::GdbStub;
+pub use keyboard::{KeyBoardEvent, Keyboard};
+pub use keymap::Keymap;
+pub use memory::Memory;
+pub use quirks::Quirks;
+pub use rewind::Rewind;
+pub use scheduler::Scheduler;
+pub use stack::Stack;
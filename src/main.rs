@@ -1,48 +1,212 @@
-mod chip8;
-mod cpu;
-mod debug_levels;
-mod display;
-mod keyboard;
-mod memory;
+#![allow(clippy::upper_case_acronyms)]
 
-use std::{io, path::Path};
+mod cli;
 
-use chip8::CHIP8;
-use cpu::CPU;
-use debug_levels::DebugLevels;
-use display::Display;
-use keyboard::Keyboard;
-use memory::Memory;
+use std::{
+    env, fs,
+    io::{self, BufReader, Write},
+    path::Path,
+    process::ExitCode,
+};
 
-fn main() -> Result<(), String> {
-    print!("Enter A Path To A Valid Rom");
+use chip8::{
+    archive, assembler, disasm, frontend::wav::WavAudio, octo, Chip8Error, DebugLevels, Debugger,
+    Display, Font, GdbStub, Keyboard, Memory, CHIP8, CPU,
+};
 
-    let mut path_file = String::new();
-    let memory = match io::stdin().read_line(&mut path_file) {
-        Ok(n) => {
-            let trimmed_path_file = path_file.trim();
-            let path = Path::new(&trimmed_path_file);
-            if path.exists() {
-                Memory::from_rom(path)
-            } else {
-                return Err("Path Does Not Exist".to_string());
-            }
+use cli::{Command, Options};
+
+fn main() -> ExitCode {
+    let result = match cli::parse(env::args().skip(1)) {
+        Ok(Command::Help) => {
+            println!("{}", cli::USAGE);
+            return ExitCode::SUCCESS;
+        }
+        Ok(Command::Run(options)) => run(&options),
+        Ok(Command::Disasm { rom, origin }) => disasm(&rom, origin),
+        Ok(Command::Asm { source, output }) => asm(&source, &output),
+        Err(message) => {
+            eprintln!("chip8: {}\n\n{}", message, cli::USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    match result {
+        Ok(()) | Err(Chip8Error::Quit | Chip8Error::Exit { .. }) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("chip8: {}", error);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(options: &Options) -> Result<(), Chip8Error> {
+    let rom = if is_octo(&options.rom) {
+        octo::compile_file(&options.rom)?
+    } else {
+        archive::read_rom(&options.rom)?
+    };
+    let mut memory = Memory::from_bytes_at(&rom, options.capacity, options.load_address)?;
+
+    // anything that is not a built-in set is taken to be a font file
+    let mut font = match &options.font {
+        Some(name) => match Font::preset(name) {
+            Some(font) => font,
+            None => Font::from_file(name)?,
+        },
+        None => Font::default(),
+    };
+    font.set_address(options.font_address);
+    memory.set_font(font)?;
+
+    let mut cpu = CPU::with_quirks(options.quirks);
+    cpu.set_pc(options.load_address);
+    let mut chip8 = CHIP8::create(cpu, memory, Display::new(), Keyboard::new());
+    chip8.set_ips(options.ips);
+    chip8.set_quick_save_path(options.rom.with_extension("state"));
+    if let Some(state) = &options.state {
+        chip8.load_state_from_file(state)?;
+    }
+    if let Some(wav) = &options.wav {
+        chip8.set_audio(Box::new(WavAudio::create(wav, options.beeper)?));
+    }
+
+    let debug_levels = DebugLevels {
+        log_all: options.trace,
+        step: options.step,
+    };
+
+    let result = if options.headless {
+        if options.debug || options.gdb.is_some() {
+            debug(&mut chip8, options, false)
+        } else {
+            run_headless(&mut chip8, &debug_levels, options.frames)
         }
-        Err(error) => return Err("Failed".to_string()),
+    } else {
+        run_windowed(&mut chip8, &debug_levels, options)
     };
 
-    let cpu = CPU::new();
-    let mut display = Display::new("CHIP-8");
-    display.clear_dispaly();
+    // a recording that could not be completed is worth reporting when the
+    // program ended normally, but not over the fault that stopped it
+    let finished = chip8.finish_audio();
+    match result {
+        Ok(()) | Err(Chip8Error::Quit | Chip8Error::Exit { .. }) => finished.and(result),
+        Err(error) => Err(error),
+    }
+}
+
+// hands control to the terminal debugger or a remote GDB until it is done
+fn debug(chip8: &mut CHIP8, options: &Options, realtime: bool) -> Result<(), Chip8Error> {
+    if let Some(port) = options.gdb {
+        let mut stub = GdbStub::new();
+        stub.set_realtime(realtime);
+        return stub.listen(chip8, ("127.0.0.1", port));
+    }
 
-    let keybaord = Keyboard::new();
+    let mut debugger = Debugger::new();
+    debugger.set_realtime(realtime);
+    debugger.repl(chip8, BufReader::new(io::stdin()), io::stdout())
+}
 
-    let mut chip8 = CHIP8::create(cpu, memory, display, keybaord);
+fn disasm(rom: &Path, origin: u16) -> Result<(), Chip8Error> {
+    let rom = archive::read_rom(rom)?;
+    write!(io::stdout().lock(), "{}", disasm::disassemble(&rom, origin))?;
+    Ok(())
+}
 
-    while let Ok(()) = chip8.execute(DebugLevels {
-        log_all: true,
-        step: false,
-    }) {}
+// Octo sources are told apart from assembly by their .8o extension
+fn is_octo(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext == "8o")
+}
 
+fn asm(source: &Path, output: &Path) -> Result<(), Chip8Error> {
+    let rom = if is_octo(source) {
+        octo::compile_file(source)?
+    } else {
+        assembler::assemble_file(source)?
+    };
+    fs::write(output, &rom)?;
+    println!("Wrote {} Bytes To {}", rom.len(), output.display());
     Ok(())
 }
+
+// runs as fast as possible with the null frontends, printing the display at the end
+fn run_headless(
+    chip8: &mut CHIP8,
+    debug_levels: &DebugLevels,
+    frames: Option<u64>,
+) -> Result<(), Chip8Error> {
+    let mut frame = 0;
+    let result = loop {
+        if frames.is_some_and(|frames| frame >= frames) {
+            break Ok(());
+        }
+        if let Err(error) = chip8.run_frame(debug_levels) {
+            break Err(error);
+        }
+        frame += 1;
+    };
+
+    chip8.get_display().dump_buffer();
+    result
+}
+
+#[cfg(feature = "sdl")]
+fn run_windowed(
+    chip8: &mut CHIP8,
+    debug_levels: &DebugLevels,
+    options: &Options,
+) -> Result<(), Chip8Error> {
+    use chip8::{frontend::sdl::SdlFrontend, Keymap, Scheduler};
+
+    let keymap = match &options.keymap {
+        Some(path) => Keymap::from_file(path)?,
+        None => Keymap::default(),
+    };
+
+    let frontend = SdlFrontend::new()?;
+    let video = frontend.video(
+        "CHIP-8",
+        chip8.get_display(),
+        options.scale,
+        options.palette,
+    )?;
+    chip8.set_video(Box::new(video));
+    chip8.set_input(Box::new(frontend.input(keymap)?));
+    if options.wav.is_none() {
+        // carry on silently rather than refuse to run without a sound card
+        match frontend.audio(options.beeper) {
+            Ok(audio) => chip8.set_audio(Box::new(audio)),
+            Err(error) => eprintln!("No Audio : {}", error),
+        }
+    }
+
+    if options.debug || options.gdb.is_some() {
+        return debug(chip8, options, true);
+    }
+
+    if options.rewind_depth > 0 {
+        chip8.set_rewind(options.rewind_depth, options.rewind_interval);
+    }
+
+    let mut scheduler = Scheduler::new();
+    loop {
+        chip8.run_frame(debug_levels)?;
+        if let Some(error) = chip8.take_quick_save_error() {
+            eprintln!("{}", error);
+        }
+        scheduler.wait();
+    }
+}
+
+#[cfg(not(feature = "sdl"))]
+fn run_windowed(
+    _chip8: &mut CHIP8,
+    _debug_levels: &DebugLevels,
+    _options: &Options,
+) -> Result<(), Chip8Error> {
+    Err(Chip8Error::Io(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "Built Without SDL Support, Run With --headless",
+    )))
+}
@@ -1,187 +1,310 @@
-use std::{
-    fs::{self},
-    ops::{Index, IndexMut},
-    path::Path,
+use std::{io::Read, ops::Range, path::Path};
+
+use crate::{
+    archive,
+    error::Chip8Error,
+    font::Font,
+    savestate::{self, StateReader, StateWriter},
 };
 
 const CAPACITY: usize = 4096;
+const XO_CAPACITY: usize = 65536;
 const OFFSET: usize = 512;
 
+#[derive(Clone)]
 pub struct Memory {
-    buffer: [u8; CAPACITY],
+    buffer: Vec<u8>,
+    // identifies the loaded program, see savestate::rom_hash
+    rom_hash: u64,
+    // where the program was loaded, which a font may not overwrite
+    program: Range<usize>,
+    font: Font,
+    // false when the memory was too small to hold the font
+    font_resident: bool,
 }
 
 impl Memory {
     pub fn new() -> Self {
+        Self::with_capacity(CAPACITY)
+    }
+
+    pub fn with_capacity(capacity: usize) -> Self {
+        let mut buffer = vec![0; capacity];
+        let font = Font::default();
+        // a memory too small to hold a font simply goes without
+        let font_resident = font.write(&mut buffer).is_ok();
         Self {
-            buffer: [0; CAPACITY],
+            buffer,
+            rom_hash: savestate::rom_hash(&[]),
+            program: OFFSET..OFFSET,
+            font,
+            font_resident,
         }
     }
 
-    pub fn from_rom<P: AsRef<Path>>(path: P) -> Self {
-        let file_buffer = fs::read(path).expect("FAILED : Could Not Read ROM");
-        let mut buffer: [u8; CAPACITY] = [0; CAPACITY];
-
-        // 0
-        buffer[0] = 0xF0;
-        buffer[1] = 0x90;
-        buffer[2] = 0x90;
-        buffer[3] = 0x90;
-        buffer[4] = 0xF0;
-
-        // 1
-        buffer[5] = 0x20;
-        buffer[6] = 0x60;
-        buffer[7] = 0x20;
-        buffer[8] = 0x20;
-        buffer[9] = 0x70;
-
-        // 2
-        buffer[10] = 0x20;
-        buffer[11] = 0x60;
-        buffer[12] = 0x20;
-        buffer[13] = 0x20;
-        buffer[14] = 0x70;
-
-        // 3
-        buffer[15] = 0xF0;
-        buffer[16] = 0x10;
-        buffer[17] = 0xF0;
-        buffer[18] = 0x10;
-        buffer[19] = 0xF0;
-
-        //4
-        buffer[20] = 0x90;
-        buffer[21] = 0x90;
-        buffer[22] = 0xF0;
-        buffer[23] = 0x10;
-        buffer[24] = 0x10;
-
-        //5
-        buffer[25] = 0xF0;
-        buffer[26] = 0x80;
-        buffer[27] = 0xF0;
-        buffer[28] = 0x10;
-        buffer[29] = 0xF0;
-
-        //6
-        buffer[30] = 0xF0;
-        buffer[31] = 0x80;
-        buffer[32] = 0xF0;
-        buffer[33] = 0x90;
-        buffer[34] = 0xF0;
-
-        //7
-        buffer[35] = 0xF0;
-        buffer[36] = 0x10;
-        buffer[37] = 0x20;
-        buffer[38] = 0x40;
-        buffer[39] = 0x40;
-
-        // 8
-        buffer[40] = 0xF0;
-        buffer[41] = 0x90;
-        buffer[42] = 0xF0;
-        buffer[43] = 0x90;
-        buffer[44] = 0xF0;
-
-        // 9
-        buffer[45] = 0xF0;
-        buffer[46] = 0x90;
-        buffer[47] = 0xF0;
-        buffer[48] = 0x10;
-        buffer[49] = 0xF0;
-
-        // A
-        buffer[50] = 0xF0;
-        buffer[51] = 0x90;
-        buffer[52] = 0xF0;
-        buffer[53] = 0x90;
-        buffer[54] = 0x90;
-
-        // B
-        buffer[55] = 0xE0;
-        buffer[56] = 0x90;
-        buffer[57] = 0xE0;
-        buffer[58] = 0x90;
-        buffer[59] = 0xE0;
-
-        // C
-        buffer[60] = 0xF0;
-        buffer[61] = 0x80;
-        buffer[62] = 0x80;
-        buffer[63] = 0x80;
-        buffer[64] = 0xF0;
-
-        // D
-        buffer[65] = 0xE0;
-        buffer[66] = 0x90;
-        buffer[67] = 0x90;
-        buffer[68] = 0x90;
-        buffer[69] = 0xE0;
-
-        // E
-        buffer[70] = 0xF0;
-        buffer[71] = 0x80;
-        buffer[72] = 0xF0;
-        buffer[73] = 0x80;
-        buffer[74] = 0xF0;
-
-        // F
-        buffer[75] = 0xF0;
-        buffer[76] = 0x80;
-        buffer[77] = 0xF0;
-        buffer[78] = 0x80;
-        buffer[79] = 0x80;
-
-        for (idx, byte) in file_buffer.into_iter().enumerate() {
-            if idx < CAPACITY - OFFSET {
-                buffer[OFFSET + idx] = byte;
-            }
+    pub fn from_rom<P: AsRef<Path>>(path: P) -> Result<Self, Chip8Error> {
+        Self::from_rom_with_capacity(path, CAPACITY)
+    }
+
+    pub fn from_rom_with_capacity<P: AsRef<Path>>(
+        path: P,
+        capacity: usize,
+    ) -> Result<Self, Chip8Error> {
+        Self::from_bytes_with_capacity(&archive::read_rom(path)?, capacity)
+    }
+
+    pub fn from_reader<R: Read>(reader: R) -> Result<Self, Chip8Error> {
+        Self::from_reader_at(reader, CAPACITY, OFFSET as u16)
+    }
+
+    pub fn from_reader_at<R: Read>(
+        mut reader: R,
+        capacity: usize,
+        origin: u16,
+    ) -> Result<Self, Chip8Error> {
+        let mut rom = Vec::new();
+        reader.read_to_end(&mut rom)?;
+        Self::from_bytes_at(&rom, capacity, origin)
+    }
+
+    // loads a program already in memory, such as one just compiled
+    pub fn from_bytes(rom: &[u8]) -> Result<Self, Chip8Error> {
+        Self::from_bytes_with_capacity(rom, CAPACITY)
+    }
+
+    pub fn from_bytes_with_capacity(
+        file_buffer: &[u8],
+        capacity: usize,
+    ) -> Result<Self, Chip8Error> {
+        Self::from_bytes_at(file_buffer, capacity, OFFSET as u16)
+    }
+
+    // loads the program at origin rather than 0x200, as the ETI-660 did at 0x600
+    pub fn from_bytes_at(
+        file_buffer: &[u8],
+        capacity: usize,
+        origin: u16,
+    ) -> Result<Self, Chip8Error> {
+        if origin as usize > capacity {
+            return Err(Chip8Error::LoadAddress {
+                address: origin,
+                capacity,
+            });
+        }
+        let origin = origin as usize;
+        let available = capacity - origin;
+        if file_buffer.len() > available {
+            return Err(Chip8Error::RomTooLarge {
+                size: file_buffer.len(),
+                capacity: available,
+            });
         }
 
-        Self { buffer }
+        let mut memory = Self::with_capacity(capacity);
+        memory.buffer[origin..origin + file_buffer.len()].copy_from_slice(file_buffer);
+        memory.rom_hash = savestate::rom_hash(file_buffer);
+        memory.program = origin..origin + file_buffer.len();
+        // a program loaded over the default font leaves no glyphs behind
+        if overlaps(&memory.program, &span(&memory.font)) {
+            memory.font_resident = false;
+        }
+        Ok(memory)
     }
 
-    #[allow(dead_code)]
     pub fn dump(&self) {
         let line_size = 16;
-        let chunks = CAPACITY / line_size;
+        let chunks = self.buffer.len() / line_size;
         for i in 0..chunks {
             for j in 0..line_size {
                 print!(" {:#06x} ", self.buffer[i * line_size + j])
             }
-            println!("");
+            println!();
         }
     }
 
-    pub fn get_cap() -> usize {
-        CAPACITY
+    pub fn get(&self, address: usize) -> Option<u8> {
+        self.buffer.get(address).copied()
+    }
+
+    pub fn get_mut(&mut self, address: usize) -> Option<&mut u8> {
+        self.buffer.get_mut(address)
+    }
+
+    pub fn get_cap(&self) -> usize {
+        self.buffer.len()
+    }
+
+    // XO-CHIP programs get the full 16-bit address space
+    pub fn get_xo_cap() -> usize {
+        XO_CAPACITY
     }
 
     pub fn get_offset() -> usize {
         OFFSET
     }
-}
-impl Index<u16> for Memory {
-    type Output = u8;
-
-    fn index(&self, index: u16) -> &Self::Output {
-        if index >= (CAPACITY) as u16 {
-            panic!(
-                "Memory Index : {} Is Out Of Bounds {}..{}",
-                index, 0, CAPACITY
-            );
+
+    // swaps the font for another, clearing where the old one was
+    pub fn set_font(&mut self, font: Font) -> Result<(), Chip8Error> {
+        if overlaps(&self.program, &span(&font)) {
+            return Err(Chip8Error::Font {
+                reason: format!(
+                    "A Font At {:#06x} Overlaps The Program At {:#06x}..{:#06x}",
+                    font.get_address(),
+                    self.program.start,
+                    self.program.end
+                ),
+            });
         }
-        &self.buffer[index as usize]
+
+        let mut buffer = self.buffer.clone();
+        // whatever of the old font the program was loaded over is the program's now
+        if self.font_resident {
+            for address in span(&self.font) {
+                if !self.program.contains(&address) {
+                    if let Some(byte) = buffer.get_mut(address) {
+                        *byte = 0;
+                    }
+                }
+            }
+        }
+        font.write(&mut buffer)?;
+        self.buffer = buffer;
+        self.font = font;
+        self.font_resident = true;
+        Ok(())
+    }
+
+    pub fn get_font(&self) -> &Font {
+        &self.font
+    }
+
+    pub fn get_rom_hash(&self) -> u64 {
+        self.rom_hash
+    }
+
+    pub(crate) fn write_state(&self, state: &mut StateWriter) {
+        state.bytes(&self.buffer);
+    }
+
+    // the capacity is fixed by the program, so a state must match it exactly
+    pub(crate) fn read_state(&mut self, state: &mut StateReader) -> Result<(), Chip8Error> {
+        state.array(&mut self.buffer)
     }
 }
+// the addresses a font takes up
+fn span(font: &Font) -> Range<usize> {
+    let start = font.get_address() as usize;
+    start..start + font.size()
+}
 
-impl IndexMut<u16> for Memory {
-    fn index_mut(&mut self, index: u16) -> &mut Self::Output {
-        if index >= (CAPACITY) as u16 {
-            panic!("Memory Index : {} Is Out Of Bounds", index);
-        }
-        &mut self.buffer[index as usize]
+fn overlaps(a: &Range<usize>, b: &Range<usize>) -> bool {
+    a.start < b.end && b.start < a.end && !a.is_empty() && !b.is_empty()
+}
+
+impl Default for Memory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn load_address_past_the_end_of_memory() {
+        assert!(matches!(
+            Memory::from_bytes_at(&[], 4096, 0x2000),
+            Err(Chip8Error::LoadAddress { .. })
+        ));
+        // a program may still end exactly at the end of memory
+        let memory = Memory::from_bytes_at(&[0xAB], 4096, 0xFFF).unwrap();
+        assert_eq!(memory.get(0xFFF), Some(0xAB));
+    }
+
+    #[test]
+    fn a_program_over_the_default_font_replaces_it() {
+        let mut memory = Memory::from_bytes_at(&[0xAB; 0x20], CAPACITY, 0x40).unwrap();
+        assert!(!memory.font_resident);
+
+        // moving the font away leaves the program, and what is left of
+        // the old glyphs, alone
+        let mut font = Font::default();
+        font.set_address(0x300);
+        memory.set_font(font).unwrap();
+        assert!(memory.buffer[0x40..0x60].iter().all(|byte| *byte == 0xAB));
+        assert_eq!(memory.get(0x00), Some(0xF0));
+        assert_eq!(memory.get(0x300), Some(0xF0));
+
+        let memory = Memory::from_bytes_at(&[0xAB], CAPACITY, 0xF0).unwrap();
+        assert!(memory.font_resident);
+        let memory = Memory::from_bytes_at(&[], CAPACITY, 0x40).unwrap();
+        assert!(memory.font_resident);
+    }
+
+    #[test]
+    fn from_reader_at_loads_at_the_origin() {
+        let memory = Memory::from_reader_at(&[0x12, 0x34][..], 4096, 0x600).unwrap();
+        assert_eq!(
+            (memory.get(0x600), memory.get(0x601)),
+            (Some(0x12), Some(0x34))
+        );
+        assert_eq!(memory.get(0x200), Some(0));
+    }
+
+    #[test]
+    fn set_font_in_a_memory_too_small_for_one() {
+        let mut memory = Memory::with_capacity(100);
+        assert!(matches!(
+            memory.set_font(Font::default()),
+            Err(Chip8Error::Font { .. })
+        ));
+        assert!(memory.buffer.iter().all(|byte| *byte == 0));
+    }
+
+    #[test]
+    fn set_font_clears_the_old_font() {
+        let mut memory = Memory::new();
+        let mut font = Font::eti_660();
+        font.set_address(0x100);
+        memory.set_font(font).unwrap();
+
+        assert!(memory.buffer[..0x100].iter().all(|byte| *byte == 0));
+        assert_eq!(memory.get(0x100), Some(0xE0));
+        assert_eq!(memory.get_font().small_glyph(1), 0x105);
+    }
+
+    #[test]
+    fn set_font_refuses_to_overwrite_the_program() {
+        let mut memory = Memory::from_bytes(&[0x12, 0x00]).unwrap();
+        let mut font = Font::default();
+        font.set_address(0x200);
+        assert!(matches!(
+            memory.set_font(font),
+            Err(Chip8Error::Font { .. })
+        ));
+        assert_eq!(memory.get(0x200), Some(0x12));
+
+        // right up against the program is fine
+        let mut font = Font::default();
+        font.set_address(0x200 - 240);
+        memory.set_font(font).unwrap();
+        assert_eq!(memory.get(0x200), Some(0x12));
+    }
+
+    #[test]
+    fn set_font_leaves_a_program_loaded_over_the_old_font() {
+        // a program sitting where the resident font is
+        let mut memory = Memory::new();
+        memory.buffer[0x40..0x60].fill(0xAB);
+        memory.program = 0x40..0x60;
+        let mut font = Font::default();
+        font.set_address(0x300);
+        memory.set_font(font).unwrap();
+
+        assert!(memory.buffer[..0x40].iter().all(|byte| *byte == 0));
+        assert!(memory.buffer[0x40..0x60].iter().all(|byte| *byte == 0xAB));
+        assert!(memory.buffer[0x60..0xF0].iter().all(|byte| *byte == 0));
+        assert_eq!(memory.get(0x300), Some(0xF0));
     }
 }
@@ -0,0 +1,997 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    fs,
+    path::Path,
+};
+
+use crate::{cpu::OpCode, error::Chip8Error, memory::Memory};
+
+// a macro expanding more often than this is assumed to expand itself
+const MAX_EXPANSIONS: usize = 10_000;
+
+// Compiles Octo source into a ROM that loads at 0x200. As in Octo, the ROM
+// starts with a jump to the `main` label, which every program must define.
+//
+// Covered: labels (`: name`, `:next`), `:const`, `:alias`, `:calc`,
+// `:byte`, `:org`, `:macro`, `:unpack`, `:call`, bare numbers as bytes,
+// every register and i assignment, `if ... then` / `if ... begin ... else
+// ... end` with == != < > <= >= key -key, `loop ... while ... again`, and
+// the SUPER-CHIP and XO-CHIP statements this emulator implements.
+// `:calc` follows Octo in evaluating right to left without precedence.
+pub fn compile(source: &str) -> Result<Vec<u8>, Chip8Error> {
+    Compiler::new("<source>", source).run()
+}
+
+pub fn compile_file<P: AsRef<Path>>(path: P) -> Result<Vec<u8>, Chip8Error> {
+    let path = path.as_ref();
+    let source = fs::read_to_string(path)?;
+    Compiler::new(&path.display().to_string(), &source).run()
+}
+
+#[derive(Clone, Debug)]
+struct Token {
+    text: String,
+    line: usize,
+}
+
+fn tokenize(source: &str) -> VecDeque<Token> {
+    let mut tokens = VecDeque::new();
+    for (idx, line) in source.lines().enumerate() {
+        let line_text = line.split('#').next().unwrap_or("");
+        tokens.extend(line_text.split_whitespace().map(|text| Token {
+            text: text.to_string(),
+            line: idx + 1,
+        }));
+    }
+    tokens
+}
+
+#[derive(Clone, Copy)]
+enum Fixup {
+    // the low 12 bits of the word at the position
+    Address,
+    // the whole word at the position, after an F000
+    Long,
+    // the byte at the position gets the top nibble of the address, under this one
+    UnpackHigh(u8),
+    UnpackLow,
+}
+
+struct Pending {
+    position: usize,
+    label: String,
+    kind: Fixup,
+    line: usize,
+}
+
+struct Macro {
+    args: Vec<String>,
+    body: Vec<Token>,
+}
+
+enum Condition {
+    // with the skip that jumps over the next instruction when it holds
+    Skip(OpCode),
+    // vf has been loaded, the comparison result is `vf == 0` or `vf != 0`
+    Flag(bool),
+}
+
+struct Loop {
+    start: u16,
+    exits: Vec<usize>,
+    line: usize,
+}
+
+struct Branch {
+    jump: usize,
+    has_else: bool,
+    line: usize,
+}
+
+struct Compiler {
+    file: String,
+    tokens: VecDeque<Token>,
+    line: usize,
+
+    rom: Vec<u8>,
+    // write position in rom, moved by :org
+    cursor: usize,
+
+    labels: HashMap<String, u16>,
+    constants: HashMap<String, i64>,
+    aliases: HashMap<String, u8>,
+    macros: HashMap<String, Macro>,
+    pending: Vec<Pending>,
+    loops: Vec<Loop>,
+    branches: Vec<Branch>,
+    expansions: usize,
+}
+
+impl Compiler {
+    fn new(file: &str, source: &str) -> Self {
+        Self {
+            file: file.to_string(),
+            tokens: tokenize(source),
+            line: 0,
+            rom: Vec::new(),
+            cursor: 0,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            aliases: HashMap::new(),
+            macros: HashMap::new(),
+            pending: Vec::new(),
+            loops: Vec::new(),
+            branches: Vec::new(),
+            expansions: 0,
+        }
+    }
+
+    fn error(&self, line: usize, reason: String) -> Chip8Error {
+        Chip8Error::Assembly {
+            location: format!("{}:{}", self.file, line),
+            reason,
+        }
+    }
+
+    fn run(mut self) -> Result<Vec<u8>, Chip8Error> {
+        // patched to jump to main once everything has been compiled
+        self.emit(OpCode::GOTO(0));
+
+        while !self.tokens.is_empty() {
+            self.statement()
+                .map_err(|reason| self.error(self.line, reason))?;
+        }
+
+        if let Some(open) = self.loops.last() {
+            return Err(self.error(open.line, "loop Without again".to_string()));
+        }
+        if let Some(open) = self.branches.last() {
+            return Err(self.error(open.line, "begin Without end".to_string()));
+        }
+
+        let main =
+            self.labels.get("main").copied().ok_or_else(|| {
+                self.error(self.line, "No `: main` Label To Start At".to_string())
+            })?;
+        self.patch(0, Fixup::Address, main)
+            .map_err(|reason| self.error(self.line, reason))?;
+
+        for pending in std::mem::take(&mut self.pending) {
+            let address = self.labels.get(&pending.label).copied().ok_or_else(|| {
+                self.error(
+                    pending.line,
+                    format!("`{}` Is Never Defined", pending.label),
+                )
+            })?;
+            self.patch(pending.position, pending.kind, address)
+                .map_err(|reason| self.error(pending.line, reason))?;
+        }
+        Ok(self.rom)
+    }
+
+    fn patch(&mut self, at: usize, kind: Fixup, address: u16) -> Result<(), String> {
+        match kind {
+            Fixup::Address => {
+                if address > 0xFFF {
+                    return Err(format!("{:#x} Is Past 0xFFF, Use i := long", address));
+                }
+                self.rom[at] |= (address >> 8) as u8;
+                self.rom[at + 1] = address as u8;
+            }
+            Fixup::Long => self.rom[at..at + 2].copy_from_slice(&address.to_be_bytes()),
+            Fixup::UnpackHigh(nibble) => self.rom[at] = nibble << 4 | (address >> 8) as u8 & 0xF,
+            Fixup::UnpackLow => self.rom[at] = address as u8,
+        }
+        Ok(())
+    }
+
+    fn here(&self) -> u16 {
+        (Memory::get_offset() + self.cursor) as u16
+    }
+
+    fn next(&mut self) -> Result<String, String> {
+        let token = self
+            .tokens
+            .pop_front()
+            .ok_or("Source Ends In The Middle Of A Statement")?;
+        self.line = token.line;
+        Ok(token.text)
+    }
+
+    fn peek(&self) -> Option<&str> {
+        self.tokens.front().map(|token| token.text.as_str())
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), String> {
+        let token = self.next()?;
+        if token != expected {
+            return Err(format!("Expected `{}`, Found `{}`", expected, token));
+        }
+        Ok(())
+    }
+
+    fn emit_byte(&mut self, byte: u8) {
+        if self.cursor < self.rom.len() {
+            self.rom[self.cursor] = byte;
+        } else {
+            self.rom.resize(self.cursor, 0);
+            self.rom.push(byte);
+        }
+        self.cursor += 1;
+    }
+
+    fn emit(&mut self, op: OpCode) {
+        for byte in u16::from(op).to_be_bytes() {
+            self.emit_byte(byte);
+        }
+    }
+
+    fn define_label(&mut self, name: String, address: u16) -> Result<(), String> {
+        if self.labels.contains_key(&name) || self.constants.contains_key(&name) {
+            return Err(format!("`{}` Is Already Defined", name));
+        }
+        self.labels.insert(name, address);
+        Ok(())
+    }
+
+    fn statement(&mut self) -> Result<(), String> {
+        let token = self.next()?;
+
+        if self.macros.contains_key(&token) {
+            return self.expand(&token);
+        }
+
+        match token.as_str() {
+            ":" => {
+                let name = self.next()?;
+                self.define_label(name, self.here())
+            }
+            ":next" => {
+                // names the second byte of the following instruction, for self modifying code
+                let name = self.next()?;
+                self.define_label(name, self.here() + 1)
+            }
+            ":const" => {
+                let name = self.next()?;
+                let value = self.value()?;
+                self.constants.insert(name, value);
+                Ok(())
+            }
+            ":calc" => {
+                let name = self.next()?;
+                self.expect("{")?;
+                let value = self.calc()?;
+                self.constants.insert(name, value);
+                Ok(())
+            }
+            ":alias" => {
+                let name = self.next()?;
+                let token = self.next()?;
+                let reg = self.register(&token)?;
+                self.aliases.insert(name, reg);
+                Ok(())
+            }
+            ":byte" => {
+                let value = self.value()?;
+                self.emit_byte(to_byte(value)?);
+                Ok(())
+            }
+            ":org" => {
+                let address = self.value()?;
+                self.cursor = address
+                    .checked_sub(Memory::get_offset() as i64)
+                    .and_then(|cursor| usize::try_from(cursor).ok())
+                    .ok_or_else(|| format!(":org {:#x} Is Below 0x200", address))?;
+                Ok(())
+            }
+            ":macro" => self.define_macro(),
+            ":unpack" => {
+                let nibble = self.value()?;
+                let nibble = u8::try_from(nibble)
+                    .ok()
+                    .filter(|n| *n < 16)
+                    .ok_or_else(|| format!("{} Is Not A Nibble", nibble))?;
+                let label = self.next()?;
+                // v0 := nibble and high address bits, v1 := low address bits
+                self.emit(OpCode::SET_CONST(0, 0));
+                self.reference(&label, self.cursor - 1, Fixup::UnpackHigh(nibble))?;
+                self.emit(OpCode::SET_CONST(1, 0));
+                self.reference(&label, self.cursor - 1, Fixup::UnpackLow)
+            }
+            ":call" => {
+                let target = self.next()?;
+                self.address_op(OpCode::CALL(0), &target)
+            }
+            ":breakpoint" | ":proto" => self.next().map(|_| ()),
+            ":monitor" => {
+                self.next()?;
+                self.next().map(|_| ())
+            }
+            "return" | ";" => {
+                self.emit(OpCode::RETURN);
+                Ok(())
+            }
+            "clear" => self.plain(OpCode::CLEAR),
+            "exit" => self.plain(OpCode::EXIT),
+            "lores" => self.plain(OpCode::LORES),
+            "hires" => self.plain(OpCode::HIRES),
+            "scroll-left" => self.plain(OpCode::SCROLL_LEFT),
+            "scroll-right" => self.plain(OpCode::SCROLL_RIGHT),
+            "audio" => self.plain(OpCode::AUDIO),
+            "scroll-down" => {
+                let n = self.nibble()?;
+                self.plain(OpCode::SCROLL_DOWN(n))
+            }
+            "plane" => {
+                let n = self.nibble()?;
+                self.plain(OpCode::PLANE(n))
+            }
+            "bcd" => self.with_register(OpCode::BCP),
+            "saveflags" => self.with_register(OpCode::SAVE_FLAGS),
+            "loadflags" => self.with_register(OpCode::LOAD_FLAGS),
+            "save" | "load" => {
+                let first = self.next()?;
+                let x = self.register(&first)?;
+                let op = if self.peek() == Some("-") {
+                    self.next()?;
+                    let last = self.next()?;
+                    let y = self.register(&last)?;
+                    match token.as_str() {
+                        "save" => OpCode::SAVE_RANGE(x, y),
+                        _ => OpCode::LOAD_RANGE(x, y),
+                    }
+                } else {
+                    match token.as_str() {
+                        "save" => OpCode::DUMP(x),
+                        _ => OpCode::LOAD(x),
+                    }
+                };
+                self.plain(op)
+            }
+            "sprite" => {
+                let (x, y) = (self.next()?, self.next()?);
+                let (x, y) = (self.register(&x)?, self.register(&y)?);
+                let n = self.nibble()?;
+                self.plain(OpCode::DRAW(x, y, n))
+            }
+            "jump" => {
+                let target = self.next()?;
+                self.address_op(OpCode::GOTO(0), &target)
+            }
+            "jump0" => {
+                let target = self.next()?;
+                self.address_op(OpCode::JUMP(0), &target)
+            }
+            "native" => {
+                let target = self.next()?;
+                self.address_op(OpCode::ROUTINE(0), &target)
+            }
+            "delay" | "buzzer" | "pitch" => {
+                self.expect(":=")?;
+                let source = self.next()?;
+                let x = self.register(&source)?;
+                self.plain(match token.as_str() {
+                    "delay" => OpCode::SET_DELAY(x),
+                    "buzzer" => OpCode::SET_SOUND(x),
+                    _ => OpCode::PITCH(x),
+                })
+            }
+            "i" => self.assign_i(),
+            "if" => self.conditional(),
+            "else" => {
+                let line = self.line;
+                let branch = self
+                    .branches
+                    .pop()
+                    .filter(|branch| !branch.has_else)
+                    .ok_or("else Without if ... begin")?;
+                let jump = self.cursor;
+                self.emit(OpCode::GOTO(0));
+                self.patch_jump(branch.jump)?;
+                self.branches.push(Branch {
+                    jump,
+                    has_else: true,
+                    line,
+                });
+                Ok(())
+            }
+            "end" => {
+                let branch = self.branches.pop().ok_or("end Without if ... begin")?;
+                self.patch_jump(branch.jump)
+            }
+            "loop" => {
+                self.loops.push(Loop {
+                    start: self.here(),
+                    exits: Vec::new(),
+                    line: self.line,
+                });
+                Ok(())
+            }
+            "while" => {
+                if self.loops.is_empty() {
+                    return Err("while Outside Of A loop".to_string());
+                }
+                // skip the exit while the condition holds
+                self.condition(true)?;
+                let exit = self.cursor;
+                self.emit(OpCode::GOTO(0));
+                if let Some(open) = self.loops.last_mut() {
+                    open.exits.push(exit);
+                }
+                Ok(())
+            }
+            "again" => {
+                let open = self.loops.pop().ok_or("again Without loop")?;
+                self.emit(OpCode::GOTO(open.start & 0xFFF));
+                for exit in open.exits {
+                    self.patch_jump(exit)?;
+                }
+                Ok(())
+            }
+            _ if self.is_register(&token) => self.assign_register(&token),
+            _ => match parse_number(&token) {
+                // a bare number is a byte of data
+                Some(value) => {
+                    let byte = to_byte(value)?;
+                    self.emit_byte(byte);
+                    Ok(())
+                }
+                // and a bare name calls the label
+                None if is_identifier(&token) => self.address_op(OpCode::CALL(0), &token),
+                None => Err(format!("Unexpected `{}`", token)),
+            },
+        }
+    }
+
+    fn plain(&mut self, op: OpCode) -> Result<(), String> {
+        self.emit(op);
+        Ok(())
+    }
+
+    fn with_register(&mut self, op: fn(u8) -> OpCode) -> Result<(), String> {
+        let token = self.next()?;
+        let x = self.register(&token)?;
+        self.plain(op(x))
+    }
+
+    fn define_macro(&mut self) -> Result<(), String> {
+        let name = self.next()?;
+        let mut args = Vec::new();
+        loop {
+            match self.next()? {
+                brace if brace == "{" => break,
+                arg => args.push(arg),
+            }
+        }
+
+        let mut body = Vec::new();
+        let mut depth = 1;
+        loop {
+            let token = self
+                .tokens
+                .pop_front()
+                .ok_or("Macro Body Is Never Closed")?;
+            self.line = token.line;
+            match token.text.as_str() {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                break;
+            }
+            body.push(token);
+        }
+
+        self.macros.insert(name, Macro { args, body });
+        Ok(())
+    }
+
+    fn expand(&mut self, name: &str) -> Result<(), String> {
+        self.expansions += 1;
+        if self.expansions > MAX_EXPANSIONS {
+            return Err(format!("Macro `{}` Expands Without End", name));
+        }
+
+        let count = self.macros[name].args.len();
+        let mut values = Vec::with_capacity(count);
+        for _ in 0..count {
+            values.push(self.next()?);
+        }
+
+        let definition = &self.macros[name];
+        let expanded: Vec<Token> = definition
+            .body
+            .iter()
+            .map(|token| {
+                let text = match definition.args.iter().position(|arg| *arg == token.text) {
+                    Some(idx) => values[idx].clone(),
+                    None => token.text.clone(),
+                };
+                Token {
+                    text,
+                    line: token.line,
+                }
+            })
+            .collect();
+
+        for token in expanded.into_iter().rev() {
+            self.tokens.push_front(token);
+        }
+        Ok(())
+    }
+
+    fn is_register(&self, token: &str) -> bool {
+        self.register(token).is_ok()
+    }
+
+    fn register(&self, token: &str) -> Result<u8, String> {
+        if let Some(reg) = self.aliases.get(token) {
+            return Ok(*reg);
+        }
+        let lower = token.to_ascii_lowercase();
+        lower
+            .strip_prefix('v')
+            .filter(|reg| reg.len() == 1)
+            .and_then(|reg| u8::from_str_radix(reg, 16).ok())
+            .ok_or_else(|| format!("`{}` Is Not A Register", token))
+    }
+
+    // a number, a constant, a defined label or a { calc } expression
+    fn value(&mut self) -> Result<i64, String> {
+        let token = self.next()?;
+        if token == "{" {
+            return self.calc();
+        }
+        self.lookup(&token)
+    }
+
+    fn lookup(&self, token: &str) -> Result<i64, String> {
+        if let Some(value) = parse_number(token) {
+            return Ok(value);
+        }
+        if token.eq_ignore_ascii_case("here") {
+            return Ok(self.here() as i64);
+        }
+        self.constants
+            .get(token)
+            .copied()
+            .or_else(|| self.labels.get(token).map(|address| *address as i64))
+            .ok_or_else(|| format!("`{}` Is Not A Number Or A Known Name", token))
+    }
+
+    fn byte(&mut self) -> Result<u8, String> {
+        let value = self.value()?;
+        to_byte(value)
+    }
+
+    fn nibble(&mut self) -> Result<u8, String> {
+        let value = self.value()?;
+        u8::try_from(value)
+            .ok()
+            .filter(|n| *n < 16)
+            .ok_or_else(|| format!("{} Does Not Fit In A Nibble", value))
+    }
+
+    // emits op with an address taken from target, now or once the label is defined
+    fn address_op(&mut self, op: OpCode, target: &str) -> Result<(), String> {
+        let position = self.cursor;
+        self.emit(op);
+        self.reference(target, position, Fixup::Address)
+    }
+
+    fn reference(&mut self, target: &str, position: usize, kind: Fixup) -> Result<(), String> {
+        match self.lookup(target) {
+            Ok(value) => {
+                let address =
+                    u16::try_from(value).map_err(|_| format!("{} Is Not An Address", value))?;
+                self.patch(position, kind, address)
+            }
+            // labels may be used before they are defined
+            Err(_) if is_identifier(target) => {
+                self.pending.push(Pending {
+                    position,
+                    label: target.to_string(),
+                    kind,
+                    line: self.line,
+                });
+                Ok(())
+            }
+            Err(reason) => Err(reason),
+        }
+    }
+
+    // the jump at position continues here
+    fn patch_jump(&mut self, position: usize) -> Result<(), String> {
+        let address = self.here();
+        if address > 0xFFF {
+            return Err("Jump Target Is Past 0xFFF".to_string());
+        }
+        self.rom[position] = 0x10 | (address >> 8) as u8;
+        self.rom[position + 1] = address as u8;
+        Ok(())
+    }
+
+    fn assign_i(&mut self) -> Result<(), String> {
+        let op = self.next()?;
+        match op.as_str() {
+            ":=" => {}
+            "+=" => {
+                let token = self.next()?;
+                let x = self.register(&token)?;
+                return self.plain(OpCode::ADDI(x));
+            }
+            _ => return Err(format!("Expected := Or += After i, Found `{}`", op)),
+        }
+
+        let token = self.next()?;
+        match token.as_str() {
+            "hex" | "bighex" => {
+                let source = self.next()?;
+                let x = self.register(&source)?;
+                self.plain(match token.as_str() {
+                    "hex" => OpCode::SPRI(x),
+                    _ => OpCode::BIG_SPRI(x),
+                })
+            }
+            "long" => {
+                let target = self.next()?;
+                self.emit(OpCode::LONG_I);
+                let position = self.cursor;
+                self.emit_byte(0);
+                self.emit_byte(0);
+                self.reference(&target, position, Fixup::Long)
+            }
+            "{" => {
+                let value = self.calc()?;
+                let address = u16::try_from(value)
+                    .ok()
+                    .filter(|address| *address <= 0xFFF)
+                    .ok_or_else(|| format!("{} Is Not A 12 Bit Address", value))?;
+                self.plain(OpCode::SETI(address))
+            }
+            _ => self.address_op(OpCode::SETI(0), &token),
+        }
+    }
+
+    fn assign_register(&mut self, target: &str) -> Result<(), String> {
+        let x = self.register(target)?;
+        let op = self.next()?;
+        let source = self.next()?;
+
+        if op == ":=" {
+            match source.as_str() {
+                "random" => {
+                    let mask = self.byte()?;
+                    return self.plain(OpCode::RAND(x, mask));
+                }
+                "key" => return self.plain(OpCode::GET_KEY(x)),
+                "delay" => return self.plain(OpCode::GET_DELAY(x)),
+                _ => {}
+            }
+        }
+
+        if let Ok(y) = self.register(&source) {
+            return self.plain(match op.as_str() {
+                ":=" => OpCode::SET_REG(x, y),
+                "+=" => OpCode::ADD_REG(x, y),
+                "-=" => OpCode::SUB_REG(x, y),
+                "=-" => OpCode::SUBTRACT(x, y),
+                "|=" => OpCode::OR(x, y),
+                "&=" => OpCode::AND(x, y),
+                "^=" => OpCode::XOR(x, y),
+                ">>=" => OpCode::BSHIFT_RGHT(x, y),
+                "<<=" => OpCode::BSHIFT_LEFT(x, y),
+                _ => return Err(format!("`{}` Is Not A Register Operator", op)),
+            });
+        }
+
+        let value = if source == "{" {
+            self.calc()?
+        } else {
+            self.lookup(&source)?
+        };
+        let byte = to_byte(value)?;
+        self.plain(match op.as_str() {
+            ":=" => OpCode::SET_CONST(x, byte),
+            "+=" => OpCode::ADD_CONST(x, byte),
+            // there is no subtract immediate, so add the negation
+            "-=" => OpCode::ADD_CONST(x, byte.wrapping_neg()),
+            _ => return Err(format!("`{}` Does Not Take A Constant", op)),
+        })
+    }
+
+    // if <condition> then <statement>, or if <condition> begin ... [else ...] end
+    fn conditional(&mut self) -> Result<(), String> {
+        let line = self.line;
+        let mut lookahead = self.tokens.iter().map(|token| token.text.as_str());
+        let begin = lookahead.find(|token| *token == "then" || *token == "begin") == Some("begin");
+
+        if begin {
+            // skip the jump past the block when the condition holds
+            self.condition(true)?;
+            self.expect("begin")?;
+            let jump = self.cursor;
+            self.emit(OpCode::GOTO(0));
+            self.branches.push(Branch {
+                jump,
+                has_else: false,
+                line,
+            });
+        } else {
+            // skip the statement when the condition does not hold
+            self.condition(false)?;
+            self.expect("then")?;
+        }
+        Ok(())
+    }
+
+    // emits a skip over the next instruction, taken when the condition is `skip_when`
+    fn condition(&mut self, skip_when: bool) -> Result<(), String> {
+        let token = self.next()?;
+        let x = self.register(&token)?;
+        let op = self.next()?;
+
+        let condition = match op.as_str() {
+            "key" => Condition::Skip(if skip_when {
+                OpCode::KEY_P(x)
+            } else {
+                OpCode::KEY_NP(x)
+            }),
+            "-key" => Condition::Skip(if skip_when {
+                OpCode::KEY_NP(x)
+            } else {
+                OpCode::KEY_P(x)
+            }),
+            "==" | "!=" => {
+                let rhs = self.next()?;
+                let equal = (op == "==") == skip_when;
+                match self.register(&rhs) {
+                    Ok(y) if equal => Condition::Skip(OpCode::EQ_REG(x, y)),
+                    Ok(y) => Condition::Skip(OpCode::NEQ_REG(x, y)),
+                    Err(_) => {
+                        let n = to_byte(self.lookup(&rhs)?)?;
+                        Condition::Skip(if equal {
+                            OpCode::EQ(x, n)
+                        } else {
+                            OpCode::NEQ(x, n)
+                        })
+                    }
+                }
+            }
+            "<" | ">" | "<=" | ">=" => {
+                if x == 0xF {
+                    return Err("vf Cannot Be Compared, It Holds The Result".to_string());
+                }
+                // load the right hand side into vf, then subtract so that the
+                // flag alone says how the two compare
+                let rhs = self.next()?;
+                match self.register(&rhs) {
+                    Ok(y) => self.emit(OpCode::SET_REG(0xF, y)),
+                    Err(_) => {
+                        let n = to_byte(self.lookup(&rhs)?)?;
+                        self.emit(OpCode::SET_CONST(0xF, n));
+                    }
+                }
+                match op.as_str() {
+                    // flag is set when x >= rhs
+                    "<" | ">=" => self.emit(OpCode::SUBTRACT(0xF, x)),
+                    // flag is set when rhs >= x
+                    _ => self.emit(OpCode::SUB_REG(0xF, x)),
+                }
+                Condition::Flag(matches!(op.as_str(), "<" | ">"))
+            }
+            _ => return Err(format!("`{}` Is Not A Comparison", op)),
+        };
+
+        let skip = match condition {
+            Condition::Skip(skip) => skip,
+            // the condition holds when vf == 0 for < and >, vf != 0 otherwise
+            Condition::Flag(zero) => {
+                if zero == skip_when {
+                    OpCode::EQ(0xF, 0)
+                } else {
+                    OpCode::NEQ(0xF, 0)
+                }
+            }
+        };
+        self.plain(skip)
+    }
+
+    // the body of a { ... } expression, evaluated right to left as Octo does
+    fn calc(&mut self) -> Result<i64, String> {
+        let mut body = Vec::new();
+        let mut depth = 1;
+        loop {
+            let token = self.next()?;
+            match token.as_str() {
+                "{" => depth += 1,
+                "}" => depth -= 1,
+                _ => {}
+            }
+            if depth == 0 {
+                break;
+            }
+            body.push(token);
+        }
+
+        let mut pos = 0;
+        let value = self.expression(&body, &mut pos)?;
+        match body.get(pos) {
+            None => Ok(value),
+            Some(token) => Err(format!("Unexpected `{}` In Expression", token)),
+        }
+    }
+
+    fn expression(&self, body: &[String], pos: &mut usize) -> Result<i64, String> {
+        let lhs = self.term(body, pos)?;
+        let Some(op) = body.get(*pos).filter(|op| BINARY.contains(&op.as_str())) else {
+            return Ok(lhs);
+        };
+        *pos += 1;
+        let rhs = self.expression(body, pos)?;
+
+        Ok(match op.as_str() {
+            "+" => lhs.wrapping_add(rhs),
+            "-" => lhs.wrapping_sub(rhs),
+            "*" => lhs.wrapping_mul(rhs),
+            "/" | "%" if rhs == 0 => return Err("Division By Zero".to_string()),
+            "/" => lhs / rhs,
+            "%" => lhs % rhs,
+            "&" => lhs & rhs,
+            "|" => lhs | rhs,
+            "^" => lhs ^ rhs,
+            "<<" => lhs.checked_shl(rhs as u32).unwrap_or(0),
+            ">>" => lhs.checked_shr(rhs as u32).unwrap_or(0),
+            "min" => lhs.min(rhs),
+            "max" => lhs.max(rhs),
+            "pow" => lhs.checked_pow(rhs as u32).unwrap_or(0),
+            "<" => (lhs < rhs) as i64,
+            ">" => (lhs > rhs) as i64,
+            "<=" => (lhs <= rhs) as i64,
+            ">=" => (lhs >= rhs) as i64,
+            "==" => (lhs == rhs) as i64,
+            _ => (lhs != rhs) as i64,
+        })
+    }
+
+    fn term(&self, body: &[String], pos: &mut usize) -> Result<i64, String> {
+        let token = body.get(*pos).ok_or("Expression Ends Too Early")?;
+        *pos += 1;
+        match token.as_str() {
+            "-" => Ok(self.term(body, pos)?.wrapping_neg()),
+            "~" => Ok(!self.term(body, pos)?),
+            "!" => Ok((self.term(body, pos)? == 0) as i64),
+            "(" => {
+                let value = self.expression(body, pos)?;
+                match body.get(*pos).map(String::as_str) {
+                    Some(")") => {
+                        *pos += 1;
+                        Ok(value)
+                    }
+                    _ => Err("Missing `)`".to_string()),
+                }
+            }
+            _ => self.lookup(token),
+        }
+    }
+}
+
+const BINARY: [&str; 19] = [
+    "+", "-", "*", "/", "%", "&", "|", "^", "<<", ">>", "min", "max", "<", ">", "<=", ">=", "==",
+    "!=", "pow",
+];
+
+fn parse_number(token: &str) -> Option<i64> {
+    let (negative, digits) = match token.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, token),
+    };
+    let value = if let Some(hex) = digits.strip_prefix("0x") {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(bin) = digits.strip_prefix("0b") {
+        i64::from_str_radix(bin, 2).ok()?
+    } else if digits.starts_with(|c: char| c.is_ascii_digit()) {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
+
+// bytes may be written signed, -1 is 0xFF
+fn to_byte(value: i64) -> Result<u8, String> {
+    if !(-128..=255).contains(&value) {
+        return Err(format!("{} Does Not Fit In A Byte", value));
+    }
+    Ok(value as u8)
+}
+
+fn is_identifier(token: &str) -> bool {
+    let mut chars = token.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn starts_with_a_jump_to_main() {
+        let rom = compile(": dot 0x80 : main i := dot loop again").unwrap();
+        assert_eq!(rom, [0x12, 0x03, 0x80, 0xA2, 0x02, 0x12, 0x05]);
+    }
+
+    #[test]
+    fn main_is_required() {
+        let error = compile("v0 := 1").unwrap_err().to_string();
+        assert!(error.contains("main"), "{}", error);
+    }
+
+    #[test]
+    fn if_then_skips_the_statement_when_false() {
+        let rom = compile(": main if v0 == 5 then v1 := 2").unwrap();
+        assert_eq!(rom, [0x12, 0x02, 0x40, 0x05, 0x61, 0x02]);
+    }
+
+    #[test]
+    fn if_then_with_a_comparison_goes_through_vf() {
+        let rom = compile(": main if v0 < 3 then v1 := 1").unwrap();
+        assert_eq!(
+            rom,
+            [0x12, 0x02, 0x6F, 0x03, 0x8F, 0x07, 0x4F, 0x00, 0x61, 0x01]
+        );
+    }
+
+    #[test]
+    fn begin_else_end_jumps_around_each_branch() {
+        let rom = compile(": main if v0 == v1 begin v2 := 1 else v2 := 2 end").unwrap();
+        assert_eq!(
+            rom,
+            [0x12, 0x02, 0x50, 0x10, 0x12, 0x0A, 0x62, 0x01, 0x12, 0x0C, 0x62, 0x02]
+        );
+    }
+
+    #[test]
+    fn loop_while_again_exits_past_the_loop() {
+        let rom = compile(": main loop v0 += 1 while v0 != 10 again").unwrap();
+        assert_eq!(
+            rom,
+            [0x12, 0x02, 0x70, 0x01, 0x40, 0x0A, 0x12, 0x0A, 0x12, 0x02]
+        );
+    }
+
+    #[test]
+    fn unclosed_blocks_are_errors() {
+        assert!(compile(": main loop v0 += 1").is_err());
+        assert!(compile(": main if v0 == 1 begin v1 := 1").is_err());
+    }
+
+    #[test]
+    fn macros_substitute_their_arguments() {
+        let rom = compile(":macro twice X { X += 1 X += 1 } : main twice v3").unwrap();
+        assert_eq!(rom, [0x12, 0x02, 0x73, 0x01, 0x73, 0x01]);
+    }
+
+    #[test]
+    fn calc_evaluates_right_to_left() {
+        // A * (2 + 1), Octo has no operator precedence
+        let rom = compile(":const A 4 :calc B { A * 2 + 1 } : main v0 := B").unwrap();
+        assert_eq!(rom, [0x12, 0x02, 0x60, 0x0C]);
+    }
+
+    #[test]
+    fn unpack_splits_an_address_over_v0_and_v1() {
+        let rom = compile(": main :unpack 0xA target : target").unwrap();
+        assert_eq!(rom, [0x12, 0x02, 0x60, 0xA2, 0x61, 0x06]);
+    }
+
+    #[test]
+    fn undefined_labels_are_errors() {
+        let error = compile(": main jump nowhere").unwrap_err().to_string();
+        assert!(error.contains("nowhere"), "{}", error);
+    }
+}
@@ -0,0 +1,94 @@
+use crate::stack::{SCHIP_DEPTH, VIP_DEPTH};
+
+// The original interpreters disagree on a handful of instructions, and ROMs
+// written for one of them often misbehave on the others. Each flag picks
+// one side of one of those disagreements.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Quirks {
+    // 8XY6/8XYE shift VY into VX, rather than shifting VX in place
+    pub shift_uses_vy: bool,
+    // FX55/FX65 leave I pointing past the last register transferred
+    pub load_store_increments_i: bool,
+    // BNNN jumps to NNN + VX (X being the high nibble of NNN) instead of NNN + V0
+    pub jump_uses_vx: bool,
+    // 8XY1/8XY2/8XY3 clear VF
+    pub vf_reset: bool,
+    // sprites are cut off at the screen edge instead of wrapping around
+    pub clip_sprites: bool,
+    // DXYN waits for the next vertical blank before drawing
+    pub display_wait: bool,
+    // FX1E sets VF when I goes past 0xFFF, as the Amiga interpreter did
+    pub addi_sets_vf: bool,
+    // how many return addresses CALL can push before the stack overflows
+    pub stack_depth: usize,
+}
+
+impl Quirks {
+    pub fn cosmac_vip() -> Self {
+        Self {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            vf_reset: true,
+            clip_sprites: true,
+            display_wait: true,
+            addi_sets_vf: false,
+            stack_depth: VIP_DEPTH,
+        }
+    }
+
+    pub fn chip48() -> Self {
+        Self {
+            shift_uses_vy: false,
+            load_store_increments_i: true,
+            jump_uses_vx: true,
+            vf_reset: false,
+            clip_sprites: true,
+            display_wait: false,
+            addi_sets_vf: false,
+            stack_depth: SCHIP_DEPTH,
+        }
+    }
+
+    pub fn superchip() -> Self {
+        Self {
+            shift_uses_vy: false,
+            load_store_increments_i: false,
+            jump_uses_vx: true,
+            vf_reset: false,
+            clip_sprites: true,
+            display_wait: false,
+            addi_sets_vf: false,
+            stack_depth: SCHIP_DEPTH,
+        }
+    }
+
+    pub fn xo_chip() -> Self {
+        Self {
+            shift_uses_vy: true,
+            load_store_increments_i: true,
+            jump_uses_vx: false,
+            vf_reset: false,
+            clip_sprites: false,
+            display_wait: false,
+            addi_sets_vf: false,
+            stack_depth: SCHIP_DEPTH,
+        }
+    }
+
+    pub fn preset(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "vip" | "cosmac" | "cosmac-vip" | "chip8" | "chip-8" => Some(Self::cosmac_vip()),
+            "chip48" | "chip-48" => Some(Self::chip48()),
+            "schip" | "superchip" | "super-chip" => Some(Self::superchip()),
+            "xochip" | "xo-chip" => Some(Self::xo_chip()),
+            _ => None,
+        }
+    }
+}
+
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::cosmac_vip()
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::VecDeque;
+
+// 30 seconds of history, one snapshot every tenth of a second
+pub const DEFAULT_DEPTH: usize = 300;
+pub const DEFAULT_INTERVAL: u32 = 6;
+
+// A ring buffer of save states taken every `interval` frames. Only the newest
+// is kept whole, every older one is stored as its difference from the one
+// after it, which is mostly zeros as little changes between snapshots.
+pub struct Rewind {
+    depth: usize,
+    interval: u32,
+    // frames run since the newest snapshot was taken
+    elapsed: u32,
+    newest: Option<Vec<u8>>,
+    // back() turns newest into the snapshot before it, and so on to front()
+    deltas: VecDeque<Vec<u8>>,
+}
+
+impl Rewind {
+    pub fn new(depth: usize, interval: u32) -> Self {
+        Self {
+            depth: depth.max(1),
+            interval: interval.max(1),
+            elapsed: 0,
+            newest: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    pub fn get_interval(&self) -> u32 {
+        self.interval
+    }
+
+    // how many snapshots are held, the newest included
+    pub fn len(&self) -> usize {
+        self.newest.as_ref().map_or(0, |_| self.deltas.len() + 1)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.newest.is_none()
+    }
+
+    // counts a finished frame, returning true when a snapshot is due
+    pub(crate) fn tick(&mut self) -> bool {
+        self.elapsed += 1;
+        self.newest.is_none() || self.elapsed >= self.interval
+    }
+
+    pub(crate) fn push(&mut self, state: Vec<u8>) {
+        if let Some(newest) = self.newest.take() {
+            self.deltas.push_back(encode(&newest, &state));
+            if self.deltas.len() >= self.depth {
+                self.deltas.pop_front();
+            }
+        }
+        self.newest = Some(state);
+        self.elapsed = 0;
+    }
+
+    // Finds the snapshot at least `frames` back, or the oldest one held, and
+    // makes it the newest. Returns it with how many frames back it really is.
+    pub(crate) fn rewind(&mut self, frames: u32) -> Option<(Vec<u8>, u32)> {
+        let mut newest = self.newest.take()?;
+        let mut rewound = self.elapsed;
+        while rewound < frames {
+            let Some(delta) = self.deltas.pop_back() else {
+                break;
+            };
+            newest = decode(&newest, &delta);
+            rewound += self.interval;
+        }
+        self.newest = Some(newest.clone());
+        self.elapsed = 0;
+        Some((newest, rewound))
+    }
+
+    pub fn clear(&mut self) {
+        self.newest = None;
+        self.deltas.clear();
+        self.elapsed = 0;
+    }
+}
+
+// A delta is the length of the older state followed by its XOR with the
+// newer one, run length encoded as pairs of u16 counts: matching bytes to
+// skip, then differing bytes copied out literally.
+fn encode(older: &[u8], newer: &[u8]) -> Vec<u8> {
+    let xor: Vec<u8> = older
+        .iter()
+        .enumerate()
+        .map(|(idx, byte)| byte ^ newer.get(idx).copied().unwrap_or(0))
+        .collect();
+
+    let mut delta = (older.len() as u32).to_le_bytes().to_vec();
+    let mut pos = 0;
+    while pos < xor.len() {
+        let same = xor[pos..]
+            .iter()
+            .take(u16::MAX as usize)
+            .take_while(|byte| **byte == 0)
+            .count();
+        pos += same;
+        let changed = xor[pos..]
+            .iter()
+            .take(u16::MAX as usize)
+            .take_while(|byte| **byte != 0)
+            .count();
+        delta.extend((same as u16).to_le_bytes());
+        delta.extend((changed as u16).to_le_bytes());
+        delta.extend(&xor[pos..pos + changed]);
+        pos += changed;
+    }
+    delta
+}
+
+fn decode(newer: &[u8], delta: &[u8]) -> Vec<u8> {
+    let len = u32::from_le_bytes([delta[0], delta[1], delta[2], delta[3]]) as usize;
+    let mut older: Vec<u8> = (0..len)
+        .map(|idx| newer.get(idx).copied().unwrap_or(0))
+        .collect();
+
+    let mut pos = 0;
+    let mut rest = &delta[4..];
+    while rest.len() >= 4 {
+        let same = u16::from_le_bytes([rest[0], rest[1]]) as usize;
+        let changed = u16::from_le_bytes([rest[2], rest[3]]) as usize;
+        pos += same;
+        for (byte, xor) in older[pos..pos + changed].iter_mut().zip(&rest[4..]) {
+            *byte ^= xor;
+        }
+        pos += changed;
+        rest = &rest[4 + changed..];
+    }
+    older
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(older: &[u8], newer: &[u8]) {
+        assert_eq!(decode(newer, &encode(older, newer)), older);
+    }
+
+    #[test]
+    fn identical_snapshots_round_trip() {
+        let state = vec![0x5A; 300];
+        let delta = encode(&state, &state);
+        // the length and a single run of matching bytes
+        assert_eq!(delta.len(), 4 + 4);
+        round_trip(&state, &state);
+    }
+
+    #[test]
+    fn fully_changed_snapshots_round_trip() {
+        let older: Vec<u8> = (0..300).map(|idx| idx as u8).collect();
+        let newer: Vec<u8> = older.iter().map(|byte| !byte).collect();
+        round_trip(&older, &newer);
+    }
+
+    #[test]
+    fn runs_past_the_count_limit_round_trip() {
+        let long = u16::MAX as usize * 3;
+        let older = vec![0x11; long];
+        round_trip(&older, &older);
+
+        let newer = vec![0xEE; long];
+        round_trip(&older, &newer);
+
+        // a long matching run, a long changed one, then a few odd bytes
+        let mut newer = older.clone();
+        newer[1_000..70_000].fill(0x22);
+        newer[long - 3] = 0;
+        round_trip(&older, &newer);
+    }
+
+    #[test]
+    fn snapshots_of_different_lengths_round_trip() {
+        round_trip(&[1, 2, 3], &[1, 2, 3, 4, 5]);
+        round_trip(&[1, 2, 3, 4, 5], &[1, 9]);
+    }
+
+    #[test]
+    fn the_oldest_snapshot_is_evicted_at_depth() {
+        let mut rewind = Rewind::new(3, 1);
+        for frame in 0..5u8 {
+            rewind.tick();
+            rewind.push(vec![frame; 8]);
+        }
+        assert_eq!(rewind.len(), 3);
+
+        // frames 0 and 1 are gone, 2 is as far back as it goes
+        let (state, rewound) = rewind.rewind(10).unwrap();
+        assert_eq!((state, rewound), (vec![2; 8], 2));
+        assert_eq!(rewind.len(), 1);
+    }
+
+    #[test]
+    fn rewind_lands_on_the_snapshot_at_least_that_old() {
+        let mut rewind = Rewind::new(10, 3);
+        // snapshots at frames 0, 3, 6 and 9, then one more frame runs
+        for frame in 0..=10u8 {
+            if rewind.tick() {
+                rewind.push(vec![frame; 4]);
+            }
+        }
+        assert_eq!(rewind.len(), 4);
+
+        assert_eq!(rewind.rewind(4), Some((vec![6; 4], 4)));
+        assert_eq!(rewind.rewind(3), Some((vec![3; 4], 3)));
+        // only frame 0 is left, so a longer rewind stops there
+        assert_eq!(rewind.rewind(100), Some((vec![0; 4], 3)));
+        assert_eq!(rewind.rewind(1), Some((vec![0; 4], 0)));
+
+        rewind.clear();
+        assert_eq!(rewind.rewind(1), None);
+    }
+}
@@ -0,0 +1,246 @@
+use crate::error::Chip8Error;
+
+const MAGIC: &[u8; 4] = b"C8SS";
+// bump whenever the layout written by any write_state changes
+pub const VERSION: u16 = 2;
+
+// FNV-1a, 64 bit. Save states remember the hash of the ROM they were made
+// with, so one is never restored on top of a different program.
+pub fn rom_hash(rom: &[u8]) -> u64 {
+    rom.iter().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+// Save states are a header of magic, version and ROM hash, followed by the
+// CPU, memory, display and keyboard sections in that order. Numbers are
+// little endian, variable length fields are prefixed with a u32 length.
+pub(crate) struct StateWriter {
+    bytes: Vec<u8>,
+}
+
+impl StateWriter {
+    pub(crate) fn new(rom_hash: u64) -> Self {
+        let mut writer = Self { bytes: Vec::new() };
+        writer.bytes.extend(MAGIC);
+        writer.u16(VERSION);
+        writer.u64(rom_hash);
+        writer
+    }
+
+    pub(crate) fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub(crate) fn bool(&mut self, value: bool) {
+        self.u8(value as u8);
+    }
+
+    pub(crate) fn u16(&mut self, value: u16) {
+        self.bytes.extend(value.to_le_bytes());
+    }
+
+    pub(crate) fn u32(&mut self, value: u32) {
+        self.bytes.extend(value.to_le_bytes());
+    }
+
+    pub(crate) fn u64(&mut self, value: u64) {
+        self.bytes.extend(value.to_le_bytes());
+    }
+
+    pub(crate) fn bytes(&mut self, value: &[u8]) {
+        self.u32(value.len() as u32);
+        self.bytes.extend(value);
+    }
+
+    pub(crate) fn finish(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+pub(crate) struct StateReader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> StateReader<'a> {
+    // checks the header, leaving the reader at the first section
+    pub(crate) fn new(bytes: &'a [u8], rom_hash: u64) -> Result<Self, Chip8Error> {
+        let mut reader = Self { bytes, pos: 0 };
+        if reader.take(MAGIC.len())? != MAGIC {
+            return Err(invalid("Not A Save State"));
+        }
+        let version = reader.u16()?;
+        if version != VERSION {
+            return Err(invalid(&format!(
+                "Version {} Is Not Supported, Expected {}",
+                version, VERSION
+            )));
+        }
+        if reader.u64()? != rom_hash {
+            return Err(invalid("It Was Made With A Different ROM"));
+        }
+        Ok(reader)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Chip8Error> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + len)
+            .ok_or_else(|| invalid("Data Ends Early"))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    pub(crate) fn u8(&mut self) -> Result<u8, Chip8Error> {
+        Ok(self.take(1)?[0])
+    }
+
+    pub(crate) fn bool(&mut self) -> Result<bool, Chip8Error> {
+        Ok(self.u8()? != 0)
+    }
+
+    pub(crate) fn u16(&mut self) -> Result<u16, Chip8Error> {
+        let bytes = self.take(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    pub(crate) fn u32(&mut self) -> Result<u32, Chip8Error> {
+        let mut bytes = [0; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_le_bytes(bytes))
+    }
+
+    pub(crate) fn u64(&mut self) -> Result<u64, Chip8Error> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    pub(crate) fn bytes(&mut self) -> Result<&'a [u8], Chip8Error> {
+        let len = self.u32()? as usize;
+        self.take(len)
+    }
+
+    // fills a fixed size field, failing if the stored length differs
+    pub(crate) fn array(&mut self, into: &mut [u8]) -> Result<(), Chip8Error> {
+        let bytes = self.bytes()?;
+        if bytes.len() != into.len() {
+            return Err(invalid("A Field Has The Wrong Size"));
+        }
+        into.copy_from_slice(bytes);
+        Ok(())
+    }
+
+    pub(crate) fn finish(self) -> Result<(), Chip8Error> {
+        if self.pos != self.bytes.len() {
+            return Err(invalid("Unexpected Data After The Last Section"));
+        }
+        Ok(())
+    }
+}
+
+pub(crate) fn invalid(reason: &str) -> Chip8Error {
+    Chip8Error::SaveState {
+        reason: reason.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn reason(error: Chip8Error) -> String {
+        match error {
+            Chip8Error::SaveState { reason } => reason,
+            other => panic!("expected a save state error, got {}", other),
+        }
+    }
+
+    fn reader_error(bytes: &[u8], rom_hash: u64) -> String {
+        match StateReader::new(bytes, rom_hash) {
+            Ok(_) => panic!("the header was accepted"),
+            Err(error) => reason(error),
+        }
+    }
+
+    #[test]
+    fn fields_round_trip() {
+        let mut writer = StateWriter::new(42);
+        writer.u8(0xAB);
+        writer.bool(true);
+        writer.u16(0x1234);
+        writer.u32(0xDEAD_BEEF);
+        writer.u64(u64::MAX - 1);
+        writer.bytes(&[1, 2, 3]);
+        writer.bytes(&[4, 5]);
+        let state = writer.finish();
+
+        let mut reader = StateReader::new(&state, 42).unwrap();
+        assert_eq!(reader.u8().unwrap(), 0xAB);
+        assert!(reader.bool().unwrap());
+        assert_eq!(reader.u16().unwrap(), 0x1234);
+        assert_eq!(reader.u32().unwrap(), 0xDEAD_BEEF);
+        assert_eq!(reader.u64().unwrap(), u64::MAX - 1);
+        assert_eq!(reader.bytes().unwrap(), [1, 2, 3]);
+        let mut array = [0; 2];
+        reader.array(&mut array).unwrap();
+        assert_eq!(array, [4, 5]);
+        reader.finish().unwrap();
+    }
+
+    #[test]
+    fn bad_headers_are_rejected() {
+        let state = StateWriter::new(42).finish();
+
+        let mut magic = state.clone();
+        magic[0] = b'X';
+        assert_eq!(reader_error(&magic, 42), "Not A Save State");
+        assert_eq!(reader_error(b"C8", 42), "Data Ends Early");
+
+        let mut version = state.clone();
+        version[4..6].copy_from_slice(&(VERSION + 1).to_le_bytes());
+        assert_eq!(
+            reader_error(&version, 42),
+            format!(
+                "Version {} Is Not Supported, Expected {}",
+                VERSION + 1,
+                VERSION
+            )
+        );
+
+        assert_eq!(reader_error(&state, 43), "It Was Made With A Different ROM");
+    }
+
+    #[test]
+    fn truncated_and_padded_bodies_are_rejected() {
+        let mut writer = StateWriter::new(0);
+        writer.bytes(&[1, 2, 3, 4]);
+        let state = writer.finish();
+
+        let mut reader = StateReader::new(&state[..state.len() - 1], 0).unwrap();
+        assert_eq!(reason(reader.bytes().unwrap_err()), "Data Ends Early");
+
+        let mut reader = StateReader::new(&state, 0).unwrap();
+        let mut array = [0; 3];
+        assert_eq!(
+            reason(reader.array(&mut array).unwrap_err()),
+            "A Field Has The Wrong Size"
+        );
+
+        let mut padded = state.clone();
+        padded.push(0);
+        let mut reader = StateReader::new(&padded, 0).unwrap();
+        reader.bytes().unwrap();
+        assert_eq!(
+            reason(reader.finish().unwrap_err()),
+            "Unexpected Data After The Last Section"
+        );
+    }
+
+    #[test]
+    fn rom_hash_tells_roms_apart() {
+        assert_eq!(rom_hash(&[]), 0xcbf2_9ce4_8422_2325);
+        assert_ne!(rom_hash(&[0x12, 0x00]), rom_hash(&[0x00, 0x12]));
+    }
+}
@@ -0,0 +1,62 @@
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+pub const FRAME_RATE: u32 = 60;
+pub const DEFAULT_IPS: u32 = 600;
+
+// Paces the host loop to FRAME_RATE frames per second, independent of how
+// long each frame took to emulate.
+pub struct Scheduler {
+    frame: Duration,
+    next: Instant,
+}
+
+impl Scheduler {
+    pub fn new() -> Self {
+        let frame = Duration::from_secs(1) / FRAME_RATE;
+        Self {
+            frame,
+            next: Instant::now() + frame,
+        }
+    }
+
+    pub fn wait(&mut self) {
+        let now = Instant::now();
+        if now < self.next {
+            thread::sleep(self.next - now);
+            self.next += self.frame;
+        } else {
+            // we fell behind (a breakpoint, a slow host), so start counting
+            // again from here rather than racing to catch up
+            self.next = now + self.frame;
+        }
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn wait_paces_frames() {
+        let start = Instant::now();
+        let mut scheduler = Scheduler::new();
+        for _ in 0..3 {
+            scheduler.wait();
+        }
+        // three whole frames, less a little for timer granularity
+        assert!(
+            start.elapsed() >= Duration::from_millis(48),
+            "{:?}",
+            start.elapsed()
+        );
+    }
+}
@@ -0,0 +1,103 @@
+// the COSMAC VIP interpreter had room for 12 nested calls,
+// CHIP-48 and SUPER-CHIP for 16
+pub const VIP_DEPTH: usize = 12;
+pub const SCHIP_DEPTH: usize = 16;
+
+// The addresses of the CALL instructions whose subroutines are still
+// running, bounded by the depth of the interpreter being emulated.
+// RETURN resumes at the instruction after the one it pops.
+#[derive(Clone, Debug)]
+pub struct Stack {
+    addresses: Vec<u16>,
+    depth: usize,
+}
+
+impl Stack {
+    pub fn new(depth: usize) -> Self {
+        Self {
+            addresses: Vec::with_capacity(depth),
+            depth,
+        }
+    }
+
+    // returns false, leaving the stack as it was, when it is already full
+    pub fn push(&mut self, address: u16) -> bool {
+        if self.addresses.len() >= self.depth {
+            return false;
+        }
+        self.addresses.push(address);
+        true
+    }
+
+    pub fn pop(&mut self) -> Option<u16> {
+        self.addresses.pop()
+    }
+
+    // oldest call first
+    pub fn addresses(&self) -> &[u16] {
+        &self.addresses
+    }
+
+    pub fn len(&self) -> usize {
+        self.addresses.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.addresses.is_empty()
+    }
+
+    pub fn depth(&self) -> usize {
+        self.depth
+    }
+
+    // the addresses must fit within the depth, as after a restore
+    pub(crate) fn set_addresses(&mut self, addresses: Vec<u16>) -> bool {
+        if addresses.len() > self.depth {
+            return false;
+        }
+        self.addresses = addresses;
+        true
+    }
+}
+
+impl Default for Stack {
+    fn default() -> Self {
+        Self::new(VIP_DEPTH)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn push_stops_at_the_depth() {
+        let mut stack = Stack::new(3);
+        assert!(stack.push(0x200));
+        assert!(stack.push(0x300));
+        assert!(stack.push(0x400));
+        assert!(!stack.push(0x500));
+        assert_eq!(stack.addresses(), [0x200, 0x300, 0x400]);
+    }
+
+    #[test]
+    fn pop_returns_the_newest_call() {
+        let mut stack = Stack::default();
+        assert_eq!(stack.depth(), VIP_DEPTH);
+        assert_eq!(stack.pop(), None);
+
+        stack.push(0x200);
+        stack.push(0x300);
+        assert_eq!(stack.pop(), Some(0x300));
+        assert_eq!(stack.pop(), Some(0x200));
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn restored_addresses_must_fit() {
+        let mut stack = Stack::new(2);
+        assert!(stack.set_addresses(vec![0x200, 0x300]));
+        assert!(!stack.set_addresses(vec![0x200, 0x300, 0x400]));
+        assert_eq!(stack.len(), 2);
+    }
+}
@@ -0,0 +1,68 @@
+use crate::{chip8::CHIP8, debug_levels::DebugLevels, error::Chip8Error, scheduler::Scheduler};
+
+// why a run of instructions came to an end
+pub enum Halt<T> {
+    // one of the checks asked to stop
+    Stopped(T),
+    // an instruction faulted, or the program ended or was quit
+    Fault(Chip8Error),
+}
+
+// Runs a CHIP8 one CHIP8::step at a time for the debuggers, which decide
+// between instructions whether to stop.
+pub struct Stepper {
+    scheduler: Option<Scheduler>,
+}
+
+impl Stepper {
+    pub fn new() -> Self {
+        Self { scheduler: None }
+    }
+
+    // pace runs at 60 frames per second, for when a window is attached
+    pub fn set_realtime(&mut self, realtime: bool) {
+        self.scheduler = realtime.then(Scheduler::new);
+    }
+
+    // Steps until a check returns Some. before is asked ahead of every
+    // instruction but the first, so a breakpoint on the instruction we are
+    // resuming from does not stop us again; after is told whether the
+    // instruction finished a frame.
+    pub fn run<T, B, A>(&mut self, chip8: &mut CHIP8, mut before: B, mut after: A) -> Halt<T>
+    where
+        B: FnMut(&CHIP8) -> Option<T>,
+        A: FnMut(&CHIP8, bool) -> Option<T>,
+    {
+        let debug_levels = DebugLevels::default();
+        let mut first = true;
+
+        loop {
+            if !first {
+                if let Some(stop) = before(chip8) {
+                    return Halt::Stopped(stop);
+                }
+            }
+            first = false;
+
+            let frame = match chip8.step(&debug_levels) {
+                Ok(frame) => frame,
+                Err(error) => return Halt::Fault(error),
+            };
+            if frame {
+                if let Some(scheduler) = &mut self.scheduler {
+                    scheduler.wait();
+                }
+            }
+
+            if let Some(stop) = after(chip8, frame) {
+                return Halt::Stopped(stop);
+            }
+        }
+    }
+}
+
+impl Default for Stepper {
+    fn default() -> Self {
+        Self::new()
+    }
+}